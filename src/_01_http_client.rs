@@ -1,34 +1,487 @@
-use reqwest;
+use std::fmt;
+use std::io::Write;
+use std::time::Duration;
+use flate2::{Compression, write::GzEncoder};
 use serde::Deserialize;
-use crate::_02_serialization::{LinksPayload, deserialize_from_json};
+use serde::de::DeserializeOwned;
+use sha2::{Digest, Sha256};
+use crate::_02_serialization::{LinksPayload, deserialize_from_json, serialize_to_json};
+use crate::_09_api_profiles::{ApiProfile, UploadMode, UploadPolicy};
+use crate::_10_delta::{LinksDelta, compute_delta, last_uploaded_path};
+use crate::_13_signing::{SIGNATURE_HEADER, TIMESTAMP_HEADER, sign_request};
+use crate::{info, warn};
 
 //#[derive(Serialize)] uses procedural macros that generate code at compile time, not runtime reflection.
 #[derive(Deserialize)]   //#[derive(Deserialize)] is a procedural macro that automatically generates code to convert data (like JSON, YAML, etc.) into your Rust struct.
 pub struct ResponsePut {
-    #[serde(rename = "Message1")]  //The #[serde(rename = "...")] annotations handle the mismatch between Rust naming conventions and the JSON field names.
+    #[serde(rename = "Message1", default)]  //The #[serde(rename = "...")] annotations handle the mismatch between Rust naming conventions and the JSON field names.
     pub message1: String,
-    #[serde(rename = "Message2")]
+    #[serde(rename = "Message2", default)]
     pub message2: String,
+    /// anything else the server adds, kept instead of rejected
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>,
 }
 
-pub async fn put_to_rest_api() -> Result<ResponsePut, Box<dyn std::error::Error>> {
-    let url = "https://rust-rest-api-endpoints.onrender.com/api/canopy";
+/// ===================== API errors =====================
+const BODY_SNIPPET_CHARS: usize = 300;
 
-    dotenvy::dotenv().ok(); //loads environment variables from a .env file
-    let api_key = std::env::var("API_KEY")?;
+/// Non-2xx answers and bodies that are not the expected JSON, e.g. a 401, a 413 or the host's HTML error page.
+#[derive(Debug)]
+pub enum ApiError {
+    Status {
+        status: u16,
+        /// message the server put into its JSON error body, if any
+        message: Option<String>,
+        body_snippet: String,
+    },
+    InvalidBody {
+        status: u16,
+        error: String,
+        body_snippet: String,
+    },
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiError::Status { status, message: Some(message), .. } => write!(f, "API returned {}: {}", status, message),
+            ApiError::Status { status, message: None, body_snippet } => write!(f, "API returned {}: {}", status, body_snippet),
+            ApiError::InvalidBody { status, error, body_snippet } => {
+                write!(f, "API returned {} with an unexpected body ({}): {}", status, error, body_snippet)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+fn body_snippet(body: &str) -> String {
+    let collapsed = body.split_whitespace().collect::<Vec<_>>().join(" ");
+    match collapsed.char_indices().nth(BODY_SNIPPET_CHARS) {
+        Some((cut, _)) => format!("{}…", &collapsed[..cut]),
+        None => collapsed,
+    }
+}
+
+/// Error text from a JSON body: our own `Message1`/`Message2`, or the usual `message`/`error`/`detail`/`title`.
+fn server_message(body: &str) -> Option<String> {
+    let json: serde_json::Value = serde_json::from_str(body).ok()?;
+    let field = |name: &str| json.get(name).and_then(|v| v.as_str()).filter(|s| !s.is_empty());
+
+    match (field("Message1"), field("Message2")) {
+        (Some(m1), Some(m2)) => return Some(format!("{} - {}", m1, m2)),
+        (Some(m), None) | (None, Some(m)) => return Some(m.to_string()),
+        (None, None) => {}
+    }
+    ["message", "error", "detail", "title"].iter().find_map(|name| field(name)).map(str::to_string)
+}
+
+/// Checks the status before decoding, so an error page never shows up as a JSON decode error.
+async fn read_json<T: DeserializeOwned>(response: reqwest::Response) -> Result<T, Box<dyn std::error::Error>> {
+    let status = response.status();
+    let body = response.text().await?;
+
+    if !status.is_success() {
+        return Err(ApiError::Status {
+            status: status.as_u16(),
+            message: server_message(&body),
+            body_snippet: body_snippet(&body),
+        }.into());
+    }
+
+    serde_json::from_str(&body).map_err(|e| {
+        ApiError::InvalidBody {
+            status: status.as_u16(),
+            error: e.to_string(),
+            body_snippet: body_snippet(&body),
+        }.into()
+    })
+}
+
+/// Same payload → same key, so the server can recognise a PUT that was retried after a lost response.
+pub fn idempotency_key(body: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(body))
+}
+
+pub fn gzip(body: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(body)?;
+    encoder.finish()
+}
+
+fn build_client(policy: &UploadPolicy) -> reqwest::Result<reqwest::Client> {
+    reqwest::Client::builder()
+        .connect_timeout(Duration::from_secs(policy.connect_timeout_secs))
+        .timeout(Duration::from_secs(policy.request_timeout_secs))
+        .build()
+}
+
+/// Network errors (including timeouts) and 5xx are worth another try; 4xx are not.
+fn is_retryable(result: &reqwest::Result<reqwest::Response>) -> bool {
+    match result {
+        Ok(response) => response.status().is_server_error(),
+        Err(e) => e.is_timeout() || e.is_connect() || e.is_request(),
+    }
+}
+
+/// Request body as sent; the idempotency key is always the hash of the uncompressed JSON.
+struct Body {
+    key: String,
+    bytes: Vec<u8>,
+    gzipped: bool,
+}
+
+/// ===================== API client =====================
+/// Typed client for the canopy endpoint of one profile. All calls go to `profile.url`:
+/// `GET` returns the stored list as `LinksPayload`, `PUT` replaces it, `PATCH` applies a `LinksDelta`.
+/// Pointing a profile at a local stub server is enough to exercise it without the hosted API.
+pub struct ApiClient<'p> {
+    profile: &'p ApiProfile,
+    client: reqwest::Client,
+    api_key: String,
+    signing_secret: Option<String>,
+    /// path + query of `profile.url`, the part covered by the signature
+    signed_path: String,
+    /// where the last uploaded snapshot (the delta base) is kept
+    results_dir: String,
+}
+
+impl<'p> ApiClient<'p> {
+    pub fn new(profile: &'p ApiProfile) -> Result<Self, Box<dyn std::error::Error>> {
+        let url = reqwest::Url::parse(&profile.url)?;
+        let signed_path = match url.query() {
+            Some(query) => format!("{}?{}", url.path(), query),
+            None => url.path().to_string(),
+        };
+        let signing_secret = match &profile.signing {
+            Some(signing) => Some(signing.secret.resolve()?),
+            None => None,
+        };
+
+        Ok(ApiClient {
+            profile,
+            client: build_client(&profile.upload)?,
+            api_key: profile.credential.resolve()?,
+            signing_secret,
+            signed_path,
+            results_dir: "CanopyResults".to_string(),
+        })
+    }
+
+    /// Keeps the last uploaded snapshot in `dir` instead of `CanopyResults`.
+    pub fn with_results_dir(mut self, dir: &str) -> Self {
+        self.results_dir = dir.to_string();
+        self
+    }
+
+    pub fn profile(&self) -> &ApiProfile {
+        self.profile
+    }
+
+    fn last_uploaded_path(&self) -> String {
+        last_uploaded_path(&self.results_dir, &self.profile.name)
+    }
+
+    /// Sends the JSON body (gzipped if the profile says so); a 415 to a gzipped body is retried as plain JSON.
+    async fn send(
+        &self,
+        method: reqwest::Method,
+        body: Option<Vec<u8>>,
+    ) -> Result<reqwest::Response, Box<dyn std::error::Error>> {
+        let Some(json) = body else {
+            return self.send_with_retry(method, None).await;
+        };
+        if !self.profile.compress {
+            return self.send_with_retry(method, Some(Body { key: idempotency_key(&json), bytes: json, gzipped: false })).await;
+        }
+
+        let compressed = Body { key: idempotency_key(&json), bytes: gzip(&json)?, gzipped: true };
+        let response = self.send_with_retry(method.clone(), Some(compressed)).await?;
+        if response.status() != reqwest::StatusCode::UNSUPPORTED_MEDIA_TYPE {
+            return Ok(response);
+        }
+
+        warn!("Server does not accept gzip, sending plain JSON");
+        self.send_with_retry(method, Some(Body { key: idempotency_key(&json), bytes: json, gzipped: false })).await
+    }
+
+    /// Sends the request, retrying network errors and 5xx with exponential backoff.
+    async fn send_with_retry(
+        &self,
+        method: reqwest::Method,
+        body: Option<Body>,
+    ) -> Result<reqwest::Response, Box<dyn std::error::Error>> {
+        let policy = &self.profile.upload;
+        let mut backoff = Duration::from_millis(policy.initial_backoff_ms);
+        let mut attempt = 0;
 
-    // Read strongly-typed payload
-    let payload: LinksPayload =
-        deserialize_from_json("CanopyResults/canopy_results.json")?;
+        loop {
+            let mut request = self
+                .client
+                .request(method.clone(), &self.profile.url)
+                .header(self.profile.header_name.as_str(), self.api_key.as_str());
+            if let Some(secret) = &self.signing_secret {
+                // fresh timestamp per attempt, so a long backoff does not push retries out of the window
+                let timestamp = chrono::Utc::now().timestamp();
+                let bytes = body.as_ref().map_or(&[][..], |b| b.bytes.as_slice());
+                let signature = sign_request(secret.as_bytes(), method.as_str(), &self.signed_path, timestamp, bytes);
+                request = request
+                    .header(TIMESTAMP_HEADER, timestamp.to_string())
+                    .header(SIGNATURE_HEADER, signature);
+            }
+            if let Some(body) = &body {
+                request = request
+                    .header("Idempotency-Key", body.key.as_str())
+                    .header(reqwest::header::CONTENT_TYPE, "application/json")
+                    .body(body.bytes.clone());
+                if body.gzipped {
+                    request = request.header(reqwest::header::CONTENT_ENCODING, "gzip");
+                }
+            }
+            let result = request.send().await;
 
-    let client = reqwest::Client::new();
-    let response = client
-        .put(url)
-        .header("X-API-KEY", api_key)
-        .json(&payload) // ✅ correct
-        .send()
-        .await?;
+            if attempt >= policy.max_retries || !is_retryable(&result) {
+                return Ok(result?);
+            }
 
-    let result: ResponsePut = response.json().await?;
+            attempt += 1;
+            match &result {
+                Ok(response) => warn!("{} got {}, retry {}/{} in {:?}", method, response.status(), attempt, policy.max_retries, backoff),
+                Err(e) => warn!("{} failed: {}, retry {}/{} in {:?}", method, e, attempt, policy.max_retries, backoff),
+            }
+            tokio::time::sleep(backoff).await;
+            backoff *= 2;
+        }
+    }
+
+    /// The list the server currently stores.
+    pub async fn get_list(&self) -> Result<LinksPayload, Box<dyn std::error::Error>> {
+        let response = self.send(reqwest::Method::GET, None).await?;
+        read_json(response).await
+    }
+
+    pub async fn put_list(&self, payload: &LinksPayload) -> Result<ResponsePut, Box<dyn std::error::Error>> {
+        let response = self.send(reqwest::Method::PUT, Some(serde_json::to_vec(payload)?)).await?;
+        read_json(response).await
+    }
+
+    /// `None` when the server rejects the delta with 409 because its list is not the delta's base.
+    pub async fn patch_delta(&self, delta: &LinksDelta) -> Result<Option<ResponsePut>, Box<dyn std::error::Error>> {
+        let response = self.send(reqwest::Method::PATCH, Some(serde_json::to_vec(delta)?)).await?;
+        match response.status() {
+            reqwest::StatusCode::CONFLICT => Ok(None),
+            _ => Ok(Some(read_json(response).await?)),
+        }
+    }
+}
+
+/// ===================== Upload =====================
+/// Uploads the payload as built in memory; the JSON file in the results dir is only a local copy.
+pub async fn put_to_rest_api(client: &ApiClient<'_>, payload: &LinksPayload) -> Result<ResponsePut, Box<dyn std::error::Error>> {
+    let result = client.put_list(payload).await?;
+    serialize_to_json(payload, &client.last_uploaded_path())?;
     Ok(result)
-}
\ No newline at end of file
+}
+
+pub enum UploadOutcome {
+    Sent(ResponsePut),
+    /// delta mode and nothing changed since the last successful upload
+    Unchanged,
+}
+
+/// Uploads according to the profile's mode. Delta mode falls back to a full `PUT` when there is no
+/// previous snapshot yet or the server answers 409 (its list is not the one the delta is based on).
+pub async fn upload_results(client: &ApiClient<'_>, current: &LinksPayload) -> Result<UploadOutcome, Box<dyn std::error::Error>> {
+    let profile = client.profile();
+    let previous: Option<LinksPayload> = match profile.mode {
+        UploadMode::Full => None,
+        UploadMode::Delta => deserialize_from_json(&client.last_uploaded_path()).ok(),
+    };
+    let Some(previous) = previous else {
+        return Ok(UploadOutcome::Sent(put_to_rest_api(client, current).await?));
+    };
+
+    let delta = compute_delta(&previous, current)?;
+    if delta.is_empty() {
+        return Ok(UploadOutcome::Unchanged);
+    }
+
+    info!("Delta: +{} / -{} link(s)", delta.added.len(), delta.removed.len());
+    let Some(result) = client.patch_delta(&delta).await? else {
+        warn!("Server has a different base, sending the full list instead");
+        return Ok(UploadOutcome::Sent(put_to_rest_api(client, current).await?));
+    };
+
+    serialize_to_json(current, &client.last_uploaded_path())?;
+    Ok(UploadOutcome::Sent(result))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::sync::{Arc, Mutex};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+    use crate::_09_api_profiles::CredentialSource;
+    use crate::_11_reconcile::verify_upload;
+
+    /// What the stub endpoint stores and the requests it saw (method, API key header).
+    #[derive(Default)]
+    struct StubState {
+        stored: Vec<String>,
+        requests: Vec<(String, Option<String>)>,
+    }
+
+    /// Reads one request: headers, then as much body as `Content-Length` says.
+    async fn read_request(stream: &mut TcpStream) -> (String, Option<String>, Vec<u8>) {
+        let mut buf = Vec::new();
+        let mut chunk = [0u8; 4096];
+        let header_end = loop {
+            let n = stream.read(&mut chunk).await.unwrap();
+            buf.extend_from_slice(&chunk[..n]);
+            if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+                break pos + 4;
+            }
+        };
+        let head = String::from_utf8_lossy(&buf[..header_end]).to_string();
+        let header = |name: &str| {
+            head.lines()
+                .find_map(|line| line.split_once(':').filter(|(k, _)| k.eq_ignore_ascii_case(name)))
+                .map(|(_, v)| v.trim().to_string())
+        };
+        let length: usize = header("content-length").and_then(|v| v.parse().ok()).unwrap_or(0);
+        while buf.len() < header_end + length {
+            let n = stream.read(&mut chunk).await.unwrap();
+            buf.extend_from_slice(&chunk[..n]);
+        }
+        let method = head.split_whitespace().next().unwrap_or_default().to_string();
+        (method, header("x-api-key"), buf[header_end..header_end + length].to_vec())
+    }
+
+    /// Canopy endpoint stub: `GET` returns the stored list, `PUT` replaces it, `PATCH` always answers 409.
+    async fn start_stub() -> (String, Arc<Mutex<StubState>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/api/canopy", listener.local_addr().unwrap());
+        let state = Arc::new(Mutex::new(StubState::default()));
+        let shared = Arc::clone(&state);
+
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let (method, api_key, body) = read_request(&mut stream).await;
+                let (status, body) = {
+                    let mut state = shared.lock().unwrap();
+                    state.requests.push((method.clone(), api_key));
+                    match method.as_str() {
+                        "GET" => ("200 OK", json_body(&state.stored)),
+                        "PUT" => {
+                            state.stored = serde_json::from_slice::<LinksPayload>(&body).unwrap().list;
+                            ("200 OK", r#"{"Message1":"stored","Message2":"ok"}"#.to_string())
+                        }
+                        _ => ("409 Conflict", r#"{"message":"different base"}"#.to_string()),
+                    }
+                };
+                let response = format!(
+                    "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status, body.len(), body
+                );
+                stream.write_all(response.as_bytes()).await.unwrap();
+                let _ = stream.shutdown().await;
+            }
+        });
+        (url, state)
+    }
+
+    fn json_body(list: &[String]) -> String {
+        serde_json::to_string(&LinksPayload { list: list.to_vec() }).unwrap()
+    }
+
+    /// Temporary results dir holding the API key file; the profile reads the key from there.
+    fn test_dir(name: &str) -> String {
+        let dir = std::env::temp_dir().join(format!("canopy-api-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("api_key"), "test-key\n").unwrap();
+        dir.to_string_lossy().to_string()
+    }
+
+    fn stub_profile(url: &str, dir: &str, mode: UploadMode) -> ApiProfile {
+        ApiProfile {
+            name: "stub".to_string(),
+            url: url.to_string(),
+            header_name: "X-API-KEY".to_string(),
+            credential: CredentialSource::File { path: format!("{}/api_key", dir) },
+            upload: UploadPolicy { max_retries: 0, ..UploadPolicy::default() },
+            mode,
+            compress: false,
+            signing: None,
+        }
+    }
+
+    fn payload(urls: &[&str]) -> LinksPayload {
+        LinksPayload { list: urls.iter().map(|u| u.to_string()).collect() }
+    }
+
+    #[tokio::test]
+    async fn put_list_is_read_back_by_get_list() {
+        let (url, state) = start_stub().await;
+        let dir = test_dir("put-get");
+        let profile = stub_profile(&url, &dir, UploadMode::Full);
+        let client = ApiClient::new(&profile).unwrap().with_results_dir(&dir);
+
+        let response = client.put_list(&payload(&["a.pdf", "b.pdf"])).await.unwrap();
+        assert_eq!(response.message1, "stored");
+        assert_eq!(client.get_list().await.unwrap().list, ["a.pdf", "b.pdf"]);
+
+        let requests = &state.lock().unwrap().requests;
+        assert_eq!(requests.len(), 2);
+        assert!(requests.iter().all(|(_, key)| key.as_deref() == Some("test-key")));
+    }
+
+    #[tokio::test]
+    async fn delta_rejected_with_409_falls_back_to_a_full_put() {
+        let (url, state) = start_stub().await;
+        let dir = test_dir("delta-409");
+        let profile = stub_profile(&url, &dir, UploadMode::Delta);
+        let client = ApiClient::new(&profile).unwrap().with_results_dir(&dir);
+        serialize_to_json(&payload(&["a.pdf"]), &client.last_uploaded_path()).unwrap();
+
+        let current = payload(&["a.pdf", "b.pdf"]);
+        assert!(matches!(upload_results(&client, &current).await.unwrap(), UploadOutcome::Sent(_)));
+
+        let methods: Vec<String> = state.lock().unwrap().requests.iter().map(|(m, _)| m.clone()).collect();
+        assert_eq!(methods, ["PATCH", "PUT"]);
+        assert_eq!(state.lock().unwrap().stored, ["a.pdf", "b.pdf"]);
+        let saved: LinksPayload = deserialize_from_json(&client.last_uploaded_path()).unwrap();
+        assert_eq!(saved.list, current.list);
+
+        // unchanged since the fallback PUT: nothing is sent
+        assert!(matches!(upload_results(&client, &current).await.unwrap(), UploadOutcome::Unchanged));
+        assert_eq!(state.lock().unwrap().requests.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn verify_upload_fails_when_the_server_list_drifted() {
+        let (url, state) = start_stub().await;
+        let dir = test_dir("verify");
+        let profile = stub_profile(&url, &dir, UploadMode::Full);
+        let client = ApiClient::new(&profile).unwrap().with_results_dir(&dir);
+        let sent = payload(&["a.pdf", "b.pdf"]);
+
+        put_to_rest_api(&client, &sent).await.unwrap();
+        verify_upload(&client, &sent).await.unwrap();
+
+        state.lock().unwrap().stored = vec!["a.pdf".into(), "c.pdf".into(), "c.pdf".into()];
+        let error = verify_upload(&client, &sent).await.unwrap_err().to_string();
+        assert!(error.contains("1 link(s) missing on the server, 1 unexpected, 1 duplicate(s)"), "{}", error);
+    }
+
+    #[test]
+    fn error_pages_are_reported_with_their_message() {
+        assert_eq!(server_message(r#"{"Message1":"bad key","Message2":""}"#).as_deref(), Some("bad key"));
+        assert_eq!(server_message(r#"{"detail":"too large"}"#).as_deref(), Some("too large"));
+        assert_eq!(server_message("<html>502</html>"), None);
+        assert_eq!(body_snippet("a\n  b\tc"), "a b c");
+    }
+}
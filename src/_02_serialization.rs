@@ -1,14 +1,157 @@
+use std::collections::BTreeMap;
 use std::fs;
-use serde::{Serialize, Deserialize};
+use serde::{Serialize, Deserialize, de::DeserializeOwned};
+
+use crate::_05_links::Area;
+use crate::_21_object_meta::ObjectMeta;
+use crate::_23_change_notices::ChangeNotice;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct LinksPayload {
     pub list: Vec<String>,
 }
 
-/// Serialize LinksPayload → JSON file
-pub fn serialize_to_json(
-    payload: &LinksPayload,
+/// Where a link was discovered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum LinkOrigin {
+    /// `<a href>` in the rendered page
+    Dom,
+    /// request or response URL from Chrome's network log
+    Network,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LinkKind {
+    Pdf,
+    DataApi,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScrapedLink {
+    pub url: String,
+    pub kind: LinkKind,
+    pub origins: Vec<LinkOrigin>,
+    /// tabs of `MAIN_URLS` the link was listed on; empty for change notices
+    #[serde(default)]
+    pub areas: Vec<Area>,
+    /// S3 headers, filled in by the metadata enrichment step
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub object: Option<ObjectMeta>,
+}
+
+/// Everything the scraper found, with origins. `LinksPayload` is the PDF-only view of it.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ScrapeResults {
+    pub links: Vec<ScrapedLink>,
+    /// saved separately as `change_notices.json`
+    #[serde(skip)]
+    pub notices: Vec<ChangeNotice>,
+}
+
+impl ScrapeResults {
+    /// Merges links found several times (e.g. both in the DOM and on the network) into one entry per URL.
+    pub fn from_tagged(tagged: impl IntoIterator<Item = (String, LinkKind, LinkOrigin)>) -> Self {
+        let mut merged: BTreeMap<String, ScrapedLink> = BTreeMap::new();
+
+        for (url, kind, origin) in tagged {
+            let link = merged.entry(url.clone()).or_insert_with(|| ScrapedLink {
+                url,
+                kind,
+                origins: Vec::new(),
+                areas: Vec::new(),
+                object: None,
+            });
+            // a URL that is a PDF anywhere is treated as a PDF
+            if kind == LinkKind::Pdf {
+                link.kind = LinkKind::Pdf;
+            }
+            if !link.origins.contains(&origin) {
+                link.origins.push(origin);
+                link.origins.sort();
+            }
+        }
+
+        ScrapeResults {
+            links: merged.into_values().collect(),
+            notices: Vec::new(),
+        }
+    }
+
+    /// Records the tab each link was listed on.
+    pub fn tag_areas(&mut self, listed: impl IntoIterator<Item = (String, Area)>) {
+        for (url, area) in listed {
+            if let Ok(i) = self.links.binary_search_by(|link| link.url.cmp(&url))
+                && !self.links[i].areas.contains(&area)
+            {
+                self.links[i].areas.push(area);
+                self.links[i].areas.sort();
+            }
+        }
+    }
+
+    fn find(&self, url: &str) -> Option<&ScrapedLink> {
+        // `from_tagged` keeps the links sorted by URL
+        let i = self.links.binary_search_by(|link| link.url.as_str().cmp(url)).ok()?;
+        self.links.get(i)
+    }
+
+    /// First tab a link was listed on (a line is normally listed on one tab only).
+    pub fn area_of(&self, url: &str) -> Option<&Area> {
+        self.find(url)?.areas.first()
+    }
+
+    /// Adds the links and notices of a partial scrape to this snapshot. Links found again gain
+    /// the newer origins, tabs and S3 headers; nothing is removed, that takes a full scrape.
+    pub fn merge(&mut self, newer: ScrapeResults) {
+        for link in newer.links {
+            match self.links.binary_search_by(|known| known.url.cmp(&link.url)) {
+                Ok(i) => {
+                    let known = &mut self.links[i];
+                    if link.kind == LinkKind::Pdf {
+                        known.kind = LinkKind::Pdf;
+                    }
+                    for origin in link.origins {
+                        if !known.origins.contains(&origin) {
+                            known.origins.push(origin);
+                        }
+                    }
+                    for area in link.areas {
+                        if !known.areas.contains(&area) {
+                            known.areas.push(area);
+                        }
+                    }
+                    known.origins.sort();
+                    known.areas.sort();
+                    known.object = link.object.or(known.object.take());
+                }
+                Err(i) => self.links.insert(i, link),
+            }
+        }
+
+        for notice in newer.notices {
+            match self.notices.iter_mut().find(|known| known.id == notice.id) {
+                Some(known) => *known = notice,
+                None => self.notices.push(notice),
+            }
+        }
+        self.notices.sort_by_key(|notice| notice.id);
+    }
+
+    pub fn to_payload(&self) -> LinksPayload {
+        LinksPayload {
+            list: self
+                .links
+                .iter()
+                .filter(|link| link.kind == LinkKind::Pdf)
+                .map(|link| link.url.clone())
+                .collect(),
+        }
+    }
+}
+
+/// Serialize LinksPayload (or any other result type) → JSON file
+pub fn serialize_to_json<T: Serialize>(
+    payload: &T,
     path: &str,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let json = serde_json::to_string_pretty(payload)?;
@@ -16,11 +159,81 @@ pub fn serialize_to_json(
     Ok(())
 }
 
-/// Deserialize JSON file → LinksPayload
-pub fn deserialize_from_json(
+/// Deserialize JSON file → LinksPayload (or any other result type)
+pub fn deserialize_from_json<T: DeserializeOwned>(
     path: &str,
-) -> Result<LinksPayload, Box<dyn std::error::Error>> {
+) -> Result<T, Box<dyn std::error::Error>> {
     let content = fs::read_to_string(path)?;
-    let payload: LinksPayload = serde_json::from_str(&content)?;
+    let payload: T = serde_json::from_str(&content)?;
     Ok(payload)
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::_05_links::AreaKind;
+
+    fn tagged(links: &[(&str, LinkKind, LinkOrigin)]) -> ScrapeResults {
+        ScrapeResults::from_tagged(links.iter().map(|(url, kind, origin)| (url.to_string(), *kind, *origin)))
+    }
+
+    fn area(tab: &str) -> Area {
+        Area { kind: AreaKind::City, tab: tab.to_string() }
+    }
+
+    #[test]
+    fn links_found_twice_become_one_entry() {
+        let results = tagged(&[
+            ("b.pdf", LinkKind::DataApi, LinkOrigin::Network),
+            ("a.pdf", LinkKind::Pdf, LinkOrigin::Dom),
+            ("b.pdf", LinkKind::Pdf, LinkOrigin::Dom),
+        ]);
+        let urls: Vec<&str> = results.links.iter().map(|l| l.url.as_str()).collect();
+        assert_eq!(urls, ["a.pdf", "b.pdf"]);
+        assert_eq!(results.links[1].kind, LinkKind::Pdf);
+        assert_eq!(results.links[1].origins, [LinkOrigin::Dom, LinkOrigin::Network]);
+    }
+
+    #[test]
+    fn a_partial_scrape_adds_to_the_snapshot() {
+        let mut snapshot = tagged(&[("a.pdf", LinkKind::Pdf, LinkOrigin::Dom), ("c.pdf", LinkKind::Pdf, LinkOrigin::Dom)]);
+        snapshot.tag_areas([("a.pdf".to_string(), area("MHD Ostrava"))]);
+        snapshot.links[0].object = Some(ObjectMeta { content_length: Some(1), last_modified: None, etag: Some("\"1\"".into()) });
+
+        let mut newer = tagged(&[("a.pdf", LinkKind::Pdf, LinkOrigin::Network), ("b.pdf", LinkKind::Pdf, LinkOrigin::Dom)]);
+        newer.tag_areas([("a.pdf".to_string(), area("MHD Opava"))]);
+        snapshot.merge(newer);
+
+        let urls: Vec<&str> = snapshot.links.iter().map(|l| l.url.as_str()).collect();
+        assert_eq!(urls, ["a.pdf", "b.pdf", "c.pdf"]);
+        let a = &snapshot.links[0];
+        assert_eq!(a.origins, [LinkOrigin::Dom, LinkOrigin::Network]);
+        assert_eq!(a.areas, [area("MHD Opava"), area("MHD Ostrava")]);
+        // the partial run did not HEAD the file, the known headers stay
+        assert_eq!(a.object.as_ref().and_then(|o| o.etag.as_deref()), Some("\"1\""));
+        assert_eq!(snapshot.area_of("b.pdf"), None);
+    }
+
+    #[test]
+    fn merged_notices_replace_older_versions_by_id() {
+        let notice = |id: i32, title: &str| ChangeNotice {
+            id,
+            url: String::new(),
+            title: title.to_string(),
+            published: None,
+            valid_from: None,
+            valid_to: None,
+            lines: Vec::new(),
+            description: String::new(),
+            pdfs: Vec::new(),
+        };
+        let mut snapshot = tagged(&[]);
+        snapshot.notices = vec![notice(2410, "old"), notice(2412, "kept")];
+        let mut newer = tagged(&[]);
+        newer.notices = vec![notice(2411, "new"), notice(2410, "updated")];
+
+        snapshot.merge(newer);
+        let titles: Vec<(i32, &str)> = snapshot.notices.iter().map(|n| (n.id, n.title.as_str())).collect();
+        assert_eq!(titles, [(2410, "updated"), (2411, "new"), (2412, "kept")]);
+    }
+}
@@ -1,9 +1,86 @@
 use thirtyfour::prelude::*;
 use serde_json::json;
+use serde::{Serialize, Deserialize};
+use std::collections::HashSet;
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::future::Future;
+use std::hash::{Hash, Hasher};
 use std::time::Duration;
 
-use crate::_02_serialization::LinksPayload;
-use crate::_05_links::{MAIN_URLS, CHANGES_BASE_URL, get_change_ids};
+use crate::_02_serialization::{LinkKind, LinkOrigin, ScrapeResults};
+use crate::_05_links::{CHANGES_BASE_URL, KODIS_BASE_URL, area_of_tab_url, rebase_url};
+use crate::_06_run_report::{FailureArtifact, RunReport};
+use crate::_07_network_log::{NetworkCapture, performance_logging_prefs};
+use crate::_08_fixtures::{FixtureRecorder, replay_phase_url};
+use crate::_23_change_notices::{ChangeNotice, parse_change_notice};
+use crate::_27_scrape_scope::{Phase, ScrapeScope};
+use crate::{info, warn};
+
+pub const CHROMEDRIVER_URL: &str = "http://localhost:9515";
+
+/// ===================== Scraper options =====================
+pub struct ScrapeOptions {
+    /// Harvest PDF and data-API URLs from Chrome's network log as well
+    pub capture_network: bool,
+    /// Save every visited page into this fixture bundle directory
+    pub record_dir: Option<String>,
+    /// Site root to scrape; the replay server's URL for offline runs
+    pub site_base_url: String,
+    /// Phases, tabs, change IDs and lines to scrape
+    pub scope: ScrapeScope,
+}
+
+impl Default for ScrapeOptions {
+    fn default() -> Self {
+        ScrapeOptions {
+            capture_network: false,
+            record_dir: None,
+            site_base_url: KODIS_BASE_URL.to_string(),
+            scope: ScrapeScope::default(),
+        }
+    }
+}
+
+/// Per-run state every page visit may touch.
+struct ScrapeState<'r> {
+    report: &'r mut RunReport,
+    network: Option<NetworkCapture>,
+    recorder: Option<FixtureRecorder>,
+    site_base_url: String,
+}
+
+impl ScrapeState<'_> {
+    /// Where to navigate for `url`; a replay server also needs to know the phase.
+    fn page_url(&self, url: &str, phase: &str) -> String {
+        match self.site_base_url == KODIS_BASE_URL {
+            true => url.to_string(),
+            false => replay_phase_url(url, phase),
+        }
+    }
+
+    /// Called once the scraper is done with a page: drains the network log and, with `record`,
+    /// saves the page with the data-API responses the browser received on it.
+    async fn after_page(&mut self, driver: &WebDriver, url: &str, phase: &str, page: usize, record: bool) {
+        let mut responses = Vec::new();
+        if let Some(capture) = self.network.as_mut() {
+            if let Err(e) = capture.drain().await {
+                warn!("Reading the network log failed: {}", e);
+            }
+            responses = capture.take_responses().await;
+        }
+
+        if let Some(recorder) = self.recorder.as_mut().filter(|_| record) {
+            let recorded = match driver.source().await {
+                Ok(html) => recorder.record_page(url, phase, page, &html, &responses),
+                Err(e) => Err(e.into()),
+            };
+            if let Err(e) = recorded {
+                warn!("Recording {} (page {}) failed: {}", url, page, e);
+            }
+        }
+    }
+}
 
 /// ===================== Helper: Wait for elements =====================
 async fn wait_for_elements(
@@ -28,9 +105,10 @@ async fn wait_for_elements(
 }
 
 /// ===================== Chrome driver setup =====================
-async fn start_chrome_driver() -> WebDriverResult<WebDriver> {
+/// `capture_network` turns on Chrome's performance log, read later by `NetworkCapture`.
+async fn start_chrome_driver(capture_network: bool) -> WebDriverResult<WebDriver> {
     let mut caps = DesiredCapabilities::chrome();
-    let chrome_options = json!({
+    let mut chrome_options = json!({
         "args": [
             "--headless=new",
             "--disable-gpu",
@@ -40,8 +118,55 @@ async fn start_chrome_driver() -> WebDriverResult<WebDriver> {
             "--window-size=1920,1080"
         ]
     });
+    if capture_network {
+        let (logging_prefs, perf_logging_prefs) = performance_logging_prefs();
+        chrome_options["perfLoggingPrefs"] = perf_logging_prefs;
+        caps.insert("goog:loggingPrefs".to_string(), logging_prefs);
+    }
     caps.insert("goog:chromeOptions".to_string(), chrome_options);
-    WebDriver::new("http://localhost:9515", caps).await
+    WebDriver::new(CHROMEDRIVER_URL, caps).await
+}
+
+/// ===================== Failure artifacts =====================
+/// Pages with fewer links than this get a screenshot and DOM dump, even if they did not fail.
+const MIN_LINKS_PER_PAGE: usize = 1;
+
+/// Saves a screenshot, the page source and the current URL of whatever the driver shows right now.
+/// Best effort: a failing capture must never abort the scrape, missing pieces are just left out.
+async fn capture_failure(driver: &WebDriver, report: &mut RunReport, page_url: &str, reason: String) {
+    if let Err(e) = fs::create_dir_all(&report.artifacts_dir) {
+        warn!("Cannot create artifacts dir {}: {}", report.artifacts_dir, e);
+        return;
+    }
+
+    let screenshot_path = report.next_artifact_path("png");
+    let screenshot = match driver.screenshot(&screenshot_path).await {
+        Ok(()) => Some(screenshot_path.display().to_string()),
+        Err(_) => None,
+    };
+
+    let source_path = report.next_artifact_path("html");
+    let page_source = match driver.source().await {
+        Ok(html) => fs::write(&source_path, html).ok().map(|_| source_path.display().to_string()),
+        Err(_) => None,
+    };
+
+    let current_url = driver.current_url().await.ok().map(|u| u.to_string());
+    let url_path = report.next_artifact_path("url.txt");
+    let url_file = current_url
+        .as_ref()
+        .and_then(|u| fs::write(&url_path, u).ok().map(|_| url_path.display().to_string()));
+
+    warn!("Saved failure artifacts for {} ({})", page_url, reason);
+
+    report.failures.push(FailureArtifact {
+        reason,
+        page_url: page_url.to_string(),
+        current_url,
+        screenshot,
+        page_source,
+        url_file,
+    });
 }
 
 /// ===================== Extract PDF links =====================
@@ -56,33 +181,84 @@ async fn extract_pdf_links(driver: &WebDriver) -> WebDriverResult<Vec<String>> {
     Ok(hrefs.into_iter().flatten().collect())
 }
 
+/// PDFs of these years are long expired; dropped wherever they are found (change pages, network log).
+const OUTDATED_YEARS: [&str; 3] = ["2022", "2023", "2024"];
+
+fn is_outdated_pdf(url: &str) -> bool {
+    OUTDATED_YEARS.iter().any(|year| url.contains(year))
+}
+
+/// Heading and visible text of a change page; `main` when the page has one, else the whole body.
+async fn read_notice_text(driver: &WebDriver) -> WebDriverResult<(Option<String>, String)> {
+    let title = match driver.find(By::Tag("h1")).await {
+        Ok(h1) => h1.text().await.ok(),
+        Err(_) => None,
+    };
+    let content = match driver.find(By::Tag("main")).await {
+        Ok(main) => main,
+        Err(_) => driver.find(By::Tag("body")).await?,
+    };
+    Ok((title, content.text().await?))
+}
+
 /// ===================== Scrape changes links =====================
-pub async fn scrape_changes_links(driver: &WebDriver) -> WebDriverResult<Vec<String>> {
-    let change_ids = get_change_ids();
+/// Most change IDs in the range do not exist, so a missing card list is normal here
+/// and only real driver errors produce failure artifacts.
+/// Besides the PDF links, every existing page is kept as a `ChangeNotice`, with or without PDFs;
+/// a page with neither cards nor a heading is an ID that does not exist.
+/// A page that fails is captured and skipped, the other pages are still scraped.
+async fn scrape_changes_links(
+    driver: &WebDriver,
+    state: &mut ScrapeState<'_>,
+    change_ids: Vec<i32>,
+) -> (Vec<String>, Vec<ChangeNotice>) {
+    let changes_base_url = rebase_url(CHANGES_BASE_URL, &state.site_base_url);
     let mut all_links = Vec::new();
+    let mut notices = Vec::new();
 
     for id in change_ids {
-        let url = format!("{}{}", CHANGES_BASE_URL, id);
-        if driver.goto(&url).await.is_ok() {
-            tokio::time::sleep(Duration::from_millis(50)).await;
-
-            let cards_present = wait_for_elements(
-                driver,
-                By::Css("ul > li > div"),
-                Duration::from_secs(45),
-                Duration::from_millis(400),
-            ).await;
-
-            if cards_present {
-                let mut links = extract_pdf_links(driver).await?;
-                links.retain(|l| l.contains("kodis-files.s3.eu-central-1.amazonaws.com/"));
-                links.retain(|l| !["2022", "2023", "2024"].iter().any(|y| l.contains(y)));
-                all_links.extend(links);
+        let url = format!("{}{}", changes_base_url, id);
+        if let Err(e) = driver.goto(&state.page_url(&url, "changes")).await {
+            capture_failure(driver, state.report, &url, format!("navigation failed: {}", e)).await;
+            continue;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let cards_present = wait_for_elements(
+            driver,
+            By::Css("ul > li > div"),
+            Duration::from_secs(45),
+            Duration::from_millis(400),
+        ).await;
+
+        let mut links = Vec::new();
+        if cards_present {
+            match extract_pdf_links(driver).await {
+                Ok(found) => links = found,
+                Err(e) => {
+                    warn!("Change {}: link extraction failed: {}", id, e);
+                    capture_failure(driver, state.report, &url, format!("link extraction failed: {}", e)).await;
+                    state.after_page(driver, &url, "changes", 1, true).await;
+                    continue;
+                }
+            }
+            links.retain(|l| l.contains("kodis-files.s3.eu-central-1.amazonaws.com/"));
+        }
+
+        match read_notice_text(driver).await {
+            Ok((title, text)) if cards_present || title.as_ref().is_some_and(|t| !t.trim().is_empty()) => {
+                notices.push(parse_change_notice(id, &url, title, &text, links.clone()));
             }
+            Ok(_) => {}
+            Err(e) => warn!("Reading change notice {} failed: {}", id, e),
         }
+        links.retain(|l| !is_outdated_pdf(l));
+        all_links.extend(links);
+
+        state.after_page(driver, &url, "changes", 1, true).await;
     }
 
-    Ok(all_links)
+    (all_links, notices)
 }
 
 /// ===================== Scrape current/future pages =====================
@@ -97,9 +273,8 @@ async fn scrape_with_future_buttons(driver: &WebDriver) -> WebDriverResult<Vec<S
     ).await;
 
     //Bang ! neni monadic bind :-), ale proste logical not
-    match cards_shown {
-        false => return Ok(Vec::new()),
-        true => {}
+    if !cards_shown {
+        return Ok(Vec::new());
     }
     let buttons = driver.find_all(By::Css("button[title='Budoucí jízdní řády']")).await?;
     let last_index = buttons.len().saturating_sub(1);
@@ -174,88 +349,208 @@ async fn scrape_current_page(driver: &WebDriver) -> WebDriverResult<Vec<String>>
     extract_pdf_links(driver).await
 }
 
-/// ===================== Scrape current/future url =====================
-async fn scrape_url_current_and_future(
-    driver: &WebDriver,
-    url: &str,
-) -> WebDriverResult<Vec<String>> {
-    driver.goto(url).await?;
+/// ===================== Paginator =====================
+/// Hard stop for the `Další` loop, in case the button never disappears.
+const MAX_PAGES_PER_TAB: usize = 50;
 
-    let mut all_links = scrape_with_future_buttons(driver).await?;
+/// Why the paginator stopped walking a tab.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PaginationEnd {
+    LastPage,
+    MaxPagesReached,
+    RepeatedContent,
+}
 
-    loop {
-        let next_clickable = match driver.find(By::LinkText("Další")).await {
-            Ok(btn) => btn.is_displayed().await.unwrap_or(false) && btn.is_enabled().await.unwrap_or(false),
-            Err(_) => false,
-        };
+/// How many pages a tab had and why the walk ended.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TabPagination {
+    pub phase: String,
+    pub url: String,
+    pub pages: usize,
+    pub links: usize,
+    pub end: PaginationEnd,
+}
 
-        if !next_clickable {
-            break;
-        }
+/// Order-independent fingerprint of the links scraped from one page.
+fn page_fingerprint(links: &[String]) -> u64 {
+    let mut sorted: Vec<&String> = links.iter().collect();
+    sorted.sort();
 
-        if let Ok(btn) = driver.find(By::LinkText("Další")).await {
-            let _ = btn.click().await;
-            let _ = wait_for_elements(
-                driver,
-                By::Css(".Card_actions__HhB_f"),
-                Duration::from_secs(25),
-                Duration::from_millis(500),
-            ).await;
-            all_links.extend(scrape_with_future_buttons(driver).await?); 
-        }
-    }
+    let mut hasher = DefaultHasher::new();
+    sorted.hash(&mut hasher);
+    hasher.finish()
+}
 
-    Ok(all_links)
+async fn find_next_button(driver: &WebDriver) -> Option<WebElement> {
+    let btn = driver.find(By::LinkText("Další")).await.ok()?;
+    let clickable = btn.is_displayed().await.unwrap_or(false) && btn.is_enabled().await.unwrap_or(false);
+    clickable.then_some(btn)
 }
 
-async fn scrape_url_current_only(
-    driver: &WebDriver,
+/// Walks all pages of a tab, calling `scrape_page` on each one.
+/// Stops on the last page, after `max_pages`, or when a page repeats the content of an earlier one
+/// (the `Další` button stayed enabled but the page did not advance).
+/// Failed pages and pages with suspiciously few links are captured into the run's artifacts.
+async fn paginate<'a, F, Fut>(
+    driver: &'a WebDriver,
+    state: &mut ScrapeState<'_>,
+    phase: &str,
     url: &str,
-) -> WebDriverResult<Vec<String>> {
-    driver.goto(url).await?;
-
-    let mut all_links = scrape_current_page(driver).await?;
+    max_pages: usize,
+    mut scrape_page: F,
+) -> WebDriverResult<(Vec<String>, TabPagination)>
+where
+    F: FnMut(&'a WebDriver) -> Fut,
+    Fut: Future<Output = WebDriverResult<Vec<String>>>,
+{
+    if let Err(e) = driver.goto(&state.page_url(url, phase)).await {
+        capture_failure(driver, state.report, url, format!("navigation failed: {}", e)).await;
+        return Err(e);
+    }
 
-    loop {
-        let next_clickable = match driver.find(By::LinkText("Další")).await {
-            Ok(btn) => btn.is_displayed().await.unwrap_or(false) && btn.is_enabled().await.unwrap_or(false),
-            Err(_) => false,
+    let mut all_links = Vec::new();
+    let mut seen_pages = HashSet::new();
+    let mut pages = 0;
+
+    let end = loop {
+        let links = match scrape_page(driver).await {
+            Ok(links) => links,
+            Err(e) => {
+                capture_failure(driver, state.report, url, format!("page {} failed: {}", pages + 1, e)).await;
+                return Err(e);
+            }
         };
 
-        if !next_clickable {
-            break;
+        if links.len() < MIN_LINKS_PER_PAGE {
+            let reason = format!("page {} yielded {} link(s)", pages + 1, links.len());
+            capture_failure(driver, state.report, url, reason).await;
+        }
+        // a repeated page is an earlier one again, so it is not recorded a second time
+        let repeated = !seen_pages.insert(page_fingerprint(&links));
+        state.after_page(driver, url, phase, pages + 1, !repeated).await;
+        if repeated {
+            break PaginationEnd::RepeatedContent;
+        }
+        pages += 1;
+        all_links.extend(links);
+
+        if pages >= max_pages {
+            break PaginationEnd::MaxPagesReached;
         }
 
-        if let Ok(btn) = driver.find(By::LinkText("Další")).await {
-            let _ = btn.click().await;
-            let _ = wait_for_elements(
-                driver,
-                By::Css(".Card_actions__HhB_f"),
-                Duration::from_secs(25),
-                Duration::from_millis(500),
-            ).await;
-            all_links.extend(scrape_current_page(driver).await?);
+        match find_next_button(driver).await {
+            Some(btn) => {
+                let _ = btn.click().await;
+                let _ = wait_for_elements(
+                    driver,
+                    By::Css(".Card_actions__HhB_f"),
+                    Duration::from_secs(25),
+                    Duration::from_millis(500),
+                ).await;
+            }
+            None => break PaginationEnd::LastPage,
         }
+    };
+
+    let pagination = TabPagination {
+        phase: phase.to_string(),
+        url: url.to_string(),
+        pages,
+        links: all_links.len(),
+        end,
+    };
+    Ok((all_links, pagination))
+}
+
+fn print_pagination(pagination: &TabPagination) {
+    match pagination.end {
+        PaginationEnd::LastPage => info!("{} → {} page(s)", pagination.url, pagination.pages),
+        end => info!("{} → {} page(s), stopped early: {:?}", pagination.url, pagination.pages, end),
     }
+}
 
-    Ok(all_links)
+/// ===================== Scrape current/future url =====================
+async fn scrape_url_current_and_future(
+    driver: &WebDriver,
+    state: &mut ScrapeState<'_>,
+    url: &str,
+) -> WebDriverResult<(Vec<String>, TabPagination)> {
+    paginate(driver, state, "currentAndFuture", url, MAX_PAGES_PER_TAB, scrape_with_future_buttons).await
 }
 
-/// ===================== Main scraper entry =====================
-pub async fn scrape_real_results_chrome() -> Result<LinksPayload, Box<dyn std::error::Error>> {
-    let driver = start_chrome_driver().await?;
+async fn scrape_url_current_only(
+    driver: &WebDriver,
+    state: &mut ScrapeState<'_>,
+    url: &str,
+) -> WebDriverResult<(Vec<String>, TabPagination)> {
+    paginate(driver, state, "current", url, MAX_PAGES_PER_TAB, scrape_current_page).await
+}
 
-    println!("=== Starting changesLinks() ===");
-    let mut all_links = scrape_changes_links(&driver).await.unwrap_or_default();
+/// ===================== Main scraper entry =====================
+/// With `capture_network`, PDF and data-API URLs seen in Chrome's network traffic are merged
+/// with the `<a href>` links; every link in the result is tagged with where it was found.
+/// With `record_dir`, every visited page is saved as a fixture bundle, together with the data-API
+/// responses read from the browser while it was on that page.
+/// Phases and tabs outside `scope` are skipped; with `scope.lines` only their links and notices are kept.
+pub async fn scrape_real_results_chrome(
+    report: &mut RunReport,
+    options: &ScrapeOptions,
+) -> Result<ScrapeResults, Box<dyn std::error::Error>> {
+    // recorded API responses come from the network log, so recording implies capturing
+    let capture_network = options.capture_network || options.record_dir.is_some();
+
+    let driver = start_chrome_driver(capture_network).await?;
+    let network = match capture_network {
+        true => Some(NetworkCapture::attach(&driver, CHROMEDRIVER_URL, options.record_dir.is_some()).await?),
+        false => None,
+    };
+    let recorder = match &options.record_dir {
+        Some(dir) => Some(FixtureRecorder::create(dir, &options.site_base_url)?),
+        None => None,
+    };
+    let mut state = ScrapeState {
+        report,
+        network,
+        recorder,
+        site_base_url: options.site_base_url.clone(),
+    };
+
+    let scope = &options.scope;
+    let mut all_links = Vec::new();
+    let mut notices = Vec::new();
+    if scope.runs(Phase::Changes) {
+        info!("=== Starting changesLinks() ===");
+        (all_links, notices) = scrape_changes_links(&driver, &mut state, scope.change_ids()).await;
+    }
 
-    println!("=== Starting currentAndFutureLinks() ===");
-    for url in MAIN_URLS {
-        all_links.extend(scrape_url_current_and_future(&driver, url).await.unwrap_or_default());
+    // (link, tab) pairs, so every link can be tagged with the area it was listed in
+    let mut listed = Vec::new();
+    let tab_urls = scope.tab_urls();
+
+    info!("=== Starting currentAndFutureLinks() ===");
+    for url in tab_urls.iter().filter(|_| scope.runs(Phase::Future)) {
+        let url = rebase_url(url, &options.site_base_url);
+        if let Ok((links, pagination)) = scrape_url_current_and_future(&driver, &mut state, &url).await {
+            print_pagination(&pagination);
+            state.report.tabs.push(pagination);
+            if let Some(area) = area_of_tab_url(&url) {
+                listed.extend(links.iter().map(|link| (link.clone(), area.clone())));
+            }
+            all_links.extend(links);
+        }
     }
 
-    println!("=== Starting currentLinks() ===");
-    for url in MAIN_URLS {
-        all_links.extend(scrape_url_current_only(&driver, url).await.unwrap_or_default());
+    info!("=== Starting currentLinks() ===");
+    for url in tab_urls.iter().filter(|_| scope.runs(Phase::Current)) {
+        let url = rebase_url(url, &options.site_base_url);
+        if let Ok((links, pagination)) = scrape_url_current_only(&driver, &mut state, &url).await {
+            print_pagination(&pagination);
+            state.report.tabs.push(pagination);
+            if let Some(area) = area_of_tab_url(&url) {
+                listed.extend(links.iter().map(|link| (link.clone(), area.clone())));
+            }
+            all_links.extend(links);
+        }
     }
 
     let _ = driver.quit().await;
@@ -263,7 +558,34 @@ pub async fn scrape_real_results_chrome() -> Result<LinksPayload, Box<dyn std::e
     all_links.sort(); //Array.sort
     all_links.dedup(); //Array.distinct  //delete duplicates
 
-    println!("=== Total unique links: {} ===", all_links.len());
+    info!("=== Total unique links: {} ===", all_links.len());
+
+    let dom_links = all_links
+        .into_iter()
+        .map(|url| (url, LinkKind::Pdf, LinkOrigin::Dom));
+    let mut network_links = state.network.map(|capture| capture.links).unwrap_or_default();
+    network_links.retain(|(url, kind, _)| *kind != LinkKind::Pdf || !is_outdated_pdf(url));
+    // a replay serves the recorded API calls itself; report them under the live site like the original run
+    if options.site_base_url != KODIS_BASE_URL {
+        for (url, _, _) in network_links.iter_mut() {
+            if let Some(rest) = url.strip_prefix(&options.site_base_url) {
+                *url = format!("{}{}", KODIS_BASE_URL, rest);
+            }
+        }
+    }
+    if !network_links.is_empty() {
+        info!("=== Network log URLs (PDF + data API): {} ===", network_links.len());
+    }
+
+    let mut results = ScrapeResults::from_tagged(dom_links.chain(network_links));
+    results.tag_areas(listed);
+    results.links.retain(|link| scope.keeps_link(&link.url));
+    results.notices = notices.into_iter().filter(|notice| scope.keeps_notice(notice)).collect();
 
-    Ok(LinksPayload { list: all_links })
-}
\ No newline at end of file
+    if let Some(recorder) = state.recorder {
+        let dir = recorder.finish()?;
+        info!("=== Fixture bundle saved to {} ===", dir.display());
+    }
+
+    Ok(results)
+}
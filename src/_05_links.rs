@@ -1,6 +1,20 @@
 //    cd c:\temp\
 //    chromedriver.exe --port=9515
 
+use chrono::NaiveDate;
+use serde::{Serialize, Deserialize};
+
+/// Root of the live site; replay runs swap it for the local fixture server
+pub const KODIS_BASE_URL: &str = "https://www.kodis.cz";
+
+/// Points a kodis.cz URL at another site root, e.g. `http://127.0.0.1:8123/lines/city?tab=...`
+pub fn rebase_url(url: &str, base_url: &str) -> String {
+    match url.strip_prefix(KODIS_BASE_URL) {
+        Some(rest) => format!("{}{}", base_url.trim_end_matches('/'), rest),
+        None => url.to_string(),
+    }
+}
+
 /// Main URLs for scraping current and future timetables
 pub const MAIN_URLS: &[&str] = &[
     "https://www.kodis.cz/lines/city?tab=MHD+Ostrava",
@@ -31,6 +45,56 @@ pub const MAIN_URLS: &[&str] = &[
     "https://www.kodis.cz/lines/boat?tab=Lodní+doprava",
 ];
 
+/// Kind of `/lines/{kind}` page a tab belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum AreaKind {
+    /// `MHD ...` city transport, including `NAD MHD`
+    City,
+    /// regional buses, tabs named by line range (`232-293`, `NAD`)
+    Region,
+    /// `S` and `R` train lines
+    Train,
+    Boat,
+}
+
+/// One tab of `MAIN_URLS`, e.g. `City` / `MHD Ostrava`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Area {
+    pub kind: AreaKind,
+    pub tab: String,
+}
+
+/// `city/MHD Ostrava`
+impl std::fmt::Display for Area {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kind = match self.kind {
+            AreaKind::City => "city",
+            AreaKind::Region => "region",
+            AreaKind::Train => "train",
+            AreaKind::Boat => "boat",
+        };
+        write!(f, "{}/{}", kind, self.tab)
+    }
+}
+
+/// Area of a tab URL like `https://www.kodis.cz/lines/city?tab=MHD+Ostrava`; works on rebased URLs too.
+pub fn area_of_tab_url(url: &str) -> Option<Area> {
+    let (path, query) = url.split_once("/lines/")?.1.split_once('?')?;
+    let kind = match path {
+        "city" => AreaKind::City,
+        "region" => AreaKind::Region,
+        "train" => AreaKind::Train,
+        "boat" => AreaKind::Boat,
+        _ => return None,
+    };
+    let tab = query.split('&').find_map(|pair| pair.strip_prefix("tab="))?;
+
+    Some(Area {
+        kind,
+        tab: tab.replace('+', " "),
+    })
+}
+
 /// Base URL for changes pages
 pub const CHANGES_BASE_URL: &str = "https://www.kodis.cz/changes/";
 
@@ -40,6 +104,43 @@ pub fn get_change_ids() -> Vec<i32> {
     std::iter::once(2115).chain(2400..2800).collect()
 }
 
+/// Line, validity and hash encoded in a KODIS PDF name, e.g. `101_2025_03_16_2025_12_13_d61fb119c2.pdf`.
+/// Names with `_v_` before the hash (`S6_2026_02_05_2026_02_09_v_bab0ecee0a.pdf`) are temporary timetables.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimetableFile {
+    pub line: String,
+    pub valid_from: NaiveDate,
+    pub valid_to: NaiveDate,
+    pub temporary: bool,
+    pub hash: String,
+}
+
+pub fn parse_timetable_url(url: &str) -> Option<TimetableFile> {
+    let file_name = url.rsplit('/').next()?;
+    let stem = file_name.strip_suffix(".pdf")?;
+    let mut parts: Vec<&str> = stem.split('_').collect();
+
+    let hash = parts.pop()?.to_string();
+    let temporary = parts.last() == Some(&"v");
+    if temporary {
+        parts.pop();
+    }
+    if parts.len() < 7 {
+        return None;
+    }
+
+    let dates = parts.split_off(parts.len() - 6);
+    let date = |y: &str, m: &str, d: &str| NaiveDate::from_ymd_opt(y.parse().ok()?, m.parse().ok()?, d.parse().ok()?);
+
+    Some(TimetableFile {
+        line: parts.join("_"),
+        valid_from: date(dates[0], dates[1], dates[2])?,
+        valid_to: date(dates[3], dates[4], dates[5])?,
+        temporary,
+        hash,
+    })
+}
+
 /*
 /// Helper function to get URLs as Vec<String> instead of &[&str]
 /// Some code might need owned Strings instead of string slices
@@ -47,3 +148,41 @@ pub fn get_main_urls_owned() -> Vec<String> {
     MAIN_URLS.iter().map(|&s| s.to_string()).collect()
 }
 */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const S3: &str = "https://kodis-files.s3.eu-central-1.amazonaws.com/";
+
+    #[test]
+    fn timetable_names_are_parsed() {
+        let file = parse_timetable_url(&format!("{}101_2025_03_16_2025_12_13_d61fb119c2.pdf", S3)).unwrap();
+        assert_eq!(file, TimetableFile {
+            line: "101".to_string(),
+            valid_from: NaiveDate::from_ymd_opt(2025, 3, 16).unwrap(),
+            valid_to: NaiveDate::from_ymd_opt(2025, 12, 13).unwrap(),
+            temporary: false,
+            hash: "d61fb119c2".to_string(),
+        });
+    }
+
+    #[test]
+    fn temporary_timetables_and_compound_lines() {
+        let file = parse_timetable_url(&format!("{}S6_2026_02_05_2026_02_09_v_bab0ecee0a.pdf", S3)).unwrap();
+        assert_eq!(file.line, "S6");
+        assert!(file.temporary);
+        assert_eq!(file.hash, "bab0ecee0a");
+
+        let file = parse_timetable_url(&format!("{}NAD_12_2026_01_01_2026_01_31_0123456789.pdf", S3)).unwrap();
+        assert_eq!(file.line, "NAD_12");
+        assert!(!file.temporary);
+    }
+
+    #[test]
+    fn other_files_are_not_timetables() {
+        assert_eq!(parse_timetable_url(&format!("{}101_2025_03_16_2025_12_13_d61fb119c2.html", S3)), None);
+        assert_eq!(parse_timetable_url(&format!("{}leaflet_2025.pdf", S3)), None);
+        assert_eq!(parse_timetable_url(&format!("{}101_2025_13_16_2025_12_13_d61fb119c2.pdf", S3)), None);
+    }
+}
@@ -0,0 +1,64 @@
+use std::path::{Path, PathBuf};
+use serde::{Serialize, Deserialize};
+
+use crate::_04_scraping_chrome::TabPagination;
+use crate::_17_link_check::LinkCheck;
+use crate::_21_object_meta::ReplacedObject;
+
+/// Debug snapshot taken when a page fails or yields suspiciously few links.
+/// Paths are relative to the working directory, so the report can link to them directly.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FailureArtifact {
+    pub reason: String,
+    pub page_url: String,
+    pub current_url: Option<String>,
+    pub screenshot: Option<String>,
+    pub page_source: Option<String>,
+    pub url_file: Option<String>,
+}
+
+/// `%Y%m%d_%H%M%S` of now; also names outbox entries and history records.
+pub fn new_run_id() -> String {
+    chrono::Local::now().format("%Y%m%d_%H%M%S").to_string()
+}
+
+/// Summary of one scraper run, saved next to the results.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RunReport {
+    pub run_id: String,
+    pub artifacts_dir: String,
+    pub tabs: Vec<TabPagination>,
+    pub failures: Vec<FailureArtifact>,
+    /// how many links the dead-link check looked at (0 = check not run)
+    #[serde(default)]
+    pub links_checked: usize,
+    /// only the links with a problem (404, non-PDF, ...)
+    #[serde(default)]
+    pub link_problems: Vec<LinkCheck>,
+    /// PDFs whose S3 object changed since the previous run without a new URL
+    #[serde(default)]
+    pub replaced_objects: Vec<ReplacedObject>,
+}
+
+impl RunReport {
+    /// Starts a new report; artifacts of this run go to `{results_dir}/artifacts/{run_id}`.
+    pub fn start(results_dir: &str) -> Self {
+        let run_id = new_run_id();
+        let artifacts_dir = format!("{}/artifacts/{}", results_dir, run_id);
+
+        RunReport {
+            run_id,
+            artifacts_dir,
+            tabs: Vec::new(),
+            failures: Vec::new(),
+            links_checked: 0,
+            link_problems: Vec::new(),
+            replaced_objects: Vec::new(),
+        }
+    }
+
+    /// Path for the next artifact file, e.g. `003.png`.
+    pub fn next_artifact_path(&self, extension: &str) -> PathBuf {
+        Path::new(&self.artifacts_dir).join(format!("{:03}.{}", self.failures.len() + 1, extension))
+    }
+}
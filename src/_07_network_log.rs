@@ -0,0 +1,226 @@
+use base64::Engine;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use thirtyfour::prelude::*;
+
+use crate::_02_serialization::{LinkKind, LinkOrigin};
+use crate::warn;
+
+/// Chrome options that turn on network events in the `performance` log.
+/// `goog:loggingPrefs` goes to the capabilities, `perfLoggingPrefs` into `goog:chromeOptions`.
+pub fn performance_logging_prefs() -> (Value, Value) {
+    let logging_prefs = json!({ "performance": "ALL" });
+    let perf_logging_prefs = json!({ "enableNetwork": true, "enablePage": false });
+    (logging_prefs, perf_logging_prefs)
+}
+
+#[derive(Deserialize)]
+struct LogResponse {
+    value: Vec<LogEntry>,
+}
+
+#[derive(Deserialize)]
+struct LogEntry {
+    message: String,
+}
+
+/// A data-API response as the browser received it, for fixture bundles.
+#[derive(Debug, Clone)]
+pub struct CapturedResponse {
+    pub url: String,
+    pub status: u16,
+    pub content_type: Option<String>,
+    pub body: Vec<u8>,
+}
+
+/// `Network.responseReceived` of a data-API call whose body is still to be read.
+struct PendingResponse {
+    request_id: String,
+    url: String,
+    status: u16,
+    content_type: Option<String>,
+}
+
+/// Reads Chrome's performance log and keeps the PDF and data-API URLs it mentions.
+///
+/// thirtyfour has no API for `/se/log`, so the log is read straight from chromedriver over HTTP.
+/// Chromedriver empties the log on every read, so `drain` should be called after each page.
+/// With `keep_responses`, the bodies of data-API responses can be read with `take_responses`
+/// while the browser still has them, i.e. before leaving the page.
+pub struct NetworkCapture {
+    client: reqwest::Client,
+    log_url: String,
+    cdp_url: String,
+    keep_responses: bool,
+    pending: Vec<PendingResponse>,
+    pub links: Vec<(String, LinkKind, LinkOrigin)>,
+}
+
+impl NetworkCapture {
+    pub async fn attach(driver: &WebDriver, driver_url: &str, keep_responses: bool) -> WebDriverResult<Self> {
+        let session_id = driver.session_id().await?;
+
+        Ok(NetworkCapture {
+            client: reqwest::Client::new(),
+            log_url: format!("{}/session/{}/se/log", driver_url, session_id),
+            cdp_url: format!("{}/session/{}/goog/cdp/execute", driver_url, session_id),
+            keep_responses,
+            pending: Vec::new(),
+            links: Vec::new(),
+        })
+    }
+
+    /// Returns how many PDF / data-API URLs were found since the previous call.
+    pub async fn drain(&mut self) -> Result<usize, Box<dyn std::error::Error>> {
+        let response: LogResponse = self
+            .client
+            .post(&self.log_url)
+            .json(&json!({ "type": "performance" }))
+            .send()
+            .await?
+            .json()
+            .await?;
+
+        let before = self.links.len();
+        for entry in &response.value {
+            let Some((url, kind)) = harvest_url(&entry.message) else {
+                continue;
+            };
+            if self.keep_responses
+                && kind == LinkKind::DataApi
+                && let Some(pending) = pending_response(&entry.message)
+            {
+                self.pending.push(pending);
+            }
+            self.links.push((url, kind, LinkOrigin::Network));
+        }
+
+        Ok(self.links.len() - before)
+    }
+
+    /// Bodies of the data-API responses drained since the previous call, read through DevTools.
+    /// Responses the browser no longer has are left out.
+    pub async fn take_responses(&mut self) -> Vec<CapturedResponse> {
+        let mut responses = Vec::new();
+        for pending in std::mem::take(&mut self.pending) {
+            match self.response_body(&pending.request_id).await {
+                Ok(body) => responses.push(CapturedResponse {
+                    url: pending.url,
+                    status: pending.status,
+                    content_type: pending.content_type,
+                    body,
+                }),
+                Err(e) => warn!("Reading the response of {} failed: {}", pending.url, e),
+            }
+        }
+        responses
+    }
+
+    async fn response_body(&self, request_id: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let response: Value = self
+            .client
+            .post(&self.cdp_url)
+            .json(&json!({ "cmd": "Network.getResponseBody", "params": { "requestId": request_id } }))
+            .send()
+            .await?
+            .json()
+            .await?;
+
+        let value = &response["value"];
+        let body = value["body"].as_str().ok_or_else(|| format!("no body in {}", response))?;
+        match value["base64Encoded"].as_bool().unwrap_or(false) {
+            true => Ok(base64::engine::general_purpose::STANDARD.decode(body)?),
+            false => Ok(body.as_bytes().to_vec()),
+        }
+    }
+}
+
+/// Request ID, status and MIME type of a `Network.responseReceived` event.
+fn pending_response(message: &str) -> Option<PendingResponse> {
+    let event: Value = serde_json::from_str(message).ok()?;
+    let params = &event["message"]["params"];
+    let response = &params["response"];
+
+    Some(PendingResponse {
+        request_id: params["requestId"].as_str()?.to_string(),
+        url: response["url"].as_str()?.to_string(),
+        status: response["status"].as_f64()? as u16,
+        content_type: response["mimeType"].as_str().map(str::to_string),
+    })
+}
+
+/// Picks the URL out of a `Network.requestWillBeSent` / `Network.responseReceived` event
+/// and decides whether it is a PDF, a data-API call, or neither.
+fn harvest_url(message: &str) -> Option<(String, LinkKind)> {
+    let event: Value = serde_json::from_str(message).ok()?;
+    let method = event["message"]["method"].as_str()?;
+    let params = &event["message"]["params"];
+
+    let (url, mime_type) = match method {
+        "Network.requestWillBeSent" => (params["request"]["url"].as_str()?, None),
+        "Network.responseReceived" => (params["response"]["url"].as_str()?, params["response"]["mimeType"].as_str()),
+        _ => return None,
+    };
+    let resource_type = params["type"].as_str().unwrap_or_default();
+
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+
+    match (mime_type, resource_type) {
+        _ if path.to_lowercase().ends_with(".pdf") => Some((url.to_string(), LinkKind::Pdf)),
+        (Some("application/pdf"), _) => Some((url.to_string(), LinkKind::Pdf)),
+        (Some(mime), "XHR" | "Fetch") if mime.contains("json") => Some((url.to_string(), LinkKind::DataApi)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(method: &str, params: Value) -> String {
+        json!({ "message": { "method": method, "params": params } }).to_string()
+    }
+
+    #[test]
+    fn pdfs_are_found_by_extension_or_mime_type() {
+        let request = event("Network.requestWillBeSent", json!({ "request": { "url": "https://kodis-files.s3.eu-central-1.amazonaws.com/101.PDF?x=1" }, "type": "Other" }));
+        assert_eq!(harvest_url(&request).map(|(_, kind)| kind), Some(LinkKind::Pdf));
+
+        let response = event("Network.responseReceived", json!({ "response": { "url": "https://example.com/download/7", "mimeType": "application/pdf" }, "type": "Document" }));
+        assert_eq!(harvest_url(&response), Some(("https://example.com/download/7".to_string(), LinkKind::Pdf)));
+    }
+
+    #[test]
+    fn json_xhr_responses_are_data_api_calls() {
+        let response = event("Network.responseReceived", json!({ "response": { "url": "https://www.kodis.cz/api/links?tab=1", "mimeType": "application/json" }, "type": "XHR" }));
+        assert_eq!(harvest_url(&response).map(|(_, kind)| kind), Some(LinkKind::DataApi));
+
+        // the request event has no MIME type yet, only the response counts
+        let request = event("Network.requestWillBeSent", json!({ "request": { "url": "https://www.kodis.cz/api/links" }, "type": "XHR" }));
+        assert_eq!(harvest_url(&request), None);
+    }
+
+    #[test]
+    fn other_urls_and_events_are_ignored() {
+        let script = event("Network.responseReceived", json!({ "response": { "url": "https://www.kodis.cz/app.js", "mimeType": "application/javascript" }, "type": "Script" }));
+        let json_document = event("Network.responseReceived", json!({ "response": { "url": "https://www.kodis.cz/manifest.json", "mimeType": "application/json" }, "type": "Document" }));
+        let other_event = event("Network.loadingFinished", json!({ "requestId": "1" }));
+
+        assert_eq!(harvest_url(&script), None);
+        assert_eq!(harvest_url(&json_document), None);
+        assert_eq!(harvest_url(&other_event), None);
+        assert_eq!(harvest_url("not json"), None);
+    }
+
+    #[test]
+    fn data_api_responses_keep_what_replay_needs() {
+        let response = event("Network.responseReceived", json!({
+            "requestId": "42.7",
+            "response": { "url": "https://www.kodis.cz/api/links", "status": 200, "mimeType": "application/json" },
+            "type": "Fetch"
+        }));
+        let pending = pending_response(&response).unwrap();
+        assert_eq!((pending.request_id.as_str(), pending.status), ("42.7", 200));
+        assert_eq!(pending.content_type.as_deref(), Some("application/json"));
+    }
+}
@@ -0,0 +1,371 @@
+use std::collections::HashMap;
+use std::fs;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use reqwest::Url;
+use serde::{Serialize, Deserialize};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use crate::_07_network_log::CapturedResponse;
+use crate::info;
+
+/// Query parameter the replay server uses to tell pages of one tab apart.
+const REPLAY_PAGE_PARAM: &str = "__replay_page";
+/// Query parameter telling the replay server which phase visits a tab; the future and the
+/// current phase see the same URL with different DOMs.
+const REPLAY_PHASE_PARAM: &str = "__replay_phase";
+
+/// One visited page: the DOM as it was after the scraper was done with it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedPage {
+    pub key: String,
+    /// `changes`, `currentAndFuture` or `current`; empty in bundles recorded before phases were kept
+    #[serde(default)]
+    pub phase: String,
+    pub page: usize,
+    pub file: String,
+    /// URLs of the data-API responses the browser received on this page
+    #[serde(default)]
+    pub api: Vec<String>,
+}
+
+/// One data-API response, as the browser received it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedResponse {
+    pub url: String,
+    pub status: u16,
+    pub content_type: Option<String>,
+    pub file: String,
+}
+
+/// `manifest.json` of a fixture bundle; the HTML and API bodies sit next to it.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FixtureManifest {
+    pub recorded_at: String,
+    pub site_base_url: String,
+    pub pages: Vec<RecordedPage>,
+    pub responses: Vec<RecordedResponse>,
+}
+
+/// Lookup key of a page: path plus decoded query, without the host, so live and replay URLs match.
+fn fixture_key(url: &Url) -> String {
+    let query: Vec<String> = url
+        .query_pairs()
+        .filter(|(k, _)| k != REPLAY_PAGE_PARAM && k != REPLAY_PHASE_PARAM)
+        .map(|(k, v)| format!("{}={}", k, v))
+        .collect();
+
+    match query.is_empty() {
+        true => url.path().to_string(),
+        false => format!("{}?{}", url.path(), query.join("&")),
+    }
+}
+
+/// URL a replay run navigates to, so the server serves the DOM recorded in `phase`.
+pub fn replay_phase_url(url: &str, phase: &str) -> String {
+    let separator = if url.contains('?') { '&' } else { '?' };
+    format!("{}{}{}={}", url, separator, REPLAY_PHASE_PARAM, phase)
+}
+
+/// ===================== Record =====================
+/// Saves every visited page, with the data-API responses the browser received on it, into a bundle directory.
+pub struct FixtureRecorder {
+    dir: PathBuf,
+    manifest: FixtureManifest,
+}
+
+impl FixtureRecorder {
+    pub fn create(dir: &str, site_base_url: &str) -> std::io::Result<Self> {
+        let dir = PathBuf::from(dir);
+        fs::create_dir_all(dir.join("pages"))?;
+        fs::create_dir_all(dir.join("api"))?;
+
+        Ok(FixtureRecorder {
+            dir,
+            manifest: FixtureManifest {
+                recorded_at: chrono::Local::now().to_rfc3339(),
+                site_base_url: site_base_url.to_string(),
+                pages: Vec::new(),
+                responses: Vec::new(),
+            },
+        })
+    }
+
+    /// `page` is 1-based; pages 2+ are the ones reached through `Další`.
+    pub fn record_page(
+        &mut self,
+        url: &str,
+        phase: &str,
+        page: usize,
+        html: &str,
+        responses: &[CapturedResponse],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let key = fixture_key(&Url::parse(url)?);
+        let file = format!("pages/{:04}.html", self.manifest.pages.len() + 1);
+        fs::write(self.dir.join(&file), html)?;
+
+        for response in responses {
+            self.record_response(response)?;
+        }
+        let api = responses.iter().map(|r| r.url.clone()).collect();
+        self.manifest.pages.push(RecordedPage { key, phase: phase.to_string(), page, file, api });
+        Ok(())
+    }
+
+    fn record_response(&mut self, response: &CapturedResponse) -> std::io::Result<()> {
+        let file = format!("api/{:04}.body", self.manifest.responses.len() + 1);
+        fs::write(self.dir.join(&file), &response.body)?;
+
+        self.manifest.responses.push(RecordedResponse {
+            url: response.url.clone(),
+            status: response.status,
+            content_type: response.content_type.clone(),
+            file,
+        });
+        Ok(())
+    }
+
+    pub fn finish(self) -> Result<PathBuf, Box<dyn std::error::Error>> {
+        let json = serde_json::to_string_pretty(&self.manifest)?;
+        fs::write(self.dir.join("manifest.json"), json)?;
+        Ok(self.dir)
+    }
+}
+
+/// ===================== Replay =====================
+enum Fixture {
+    Page { page: ReplayPage, has_next: bool, number: usize },
+    Response { status: u16, content_type: Option<String>, body: Vec<u8> },
+}
+
+/// A recorded DOM and the site-relative data-API URLs it requested.
+#[derive(Clone)]
+struct ReplayPage {
+    html: String,
+    api: Vec<String>,
+}
+
+/// Loaded bundle, keyed by `fixture_key` (+ phase and page number for pages).
+pub struct FixtureBundle {
+    pages: HashMap<(String, String, usize), ReplayPage>,
+    responses: HashMap<String, (u16, Option<String>, Vec<u8>)>,
+}
+
+impl FixtureBundle {
+    pub fn load(dir: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let dir = Path::new(dir);
+        let manifest: FixtureManifest = serde_json::from_str(&fs::read_to_string(dir.join("manifest.json"))?)?;
+
+        // only responses from the site itself can be served; other hosts stay in the bundle for reference
+        let site_path = |url: &str| url.strip_prefix(&manifest.site_base_url).map(str::to_string);
+        let mut responses = HashMap::new();
+        for response in &manifest.responses {
+            if let Some(path) = site_path(&response.url) {
+                let key = fixture_key(&Url::parse("http://replay")?.join(&path)?);
+                let body = fs::read(dir.join(&response.file))?;
+                responses.insert(key, (response.status, response.content_type.clone(), body));
+            }
+        }
+
+        let mut pages = HashMap::new();
+        for page in &manifest.pages {
+            let html = fs::read_to_string(dir.join(&page.file))?;
+            let api = page.api.iter().filter_map(|url| site_path(url)).collect();
+            pages.insert((page.key.clone(), page.phase.clone(), page.page), ReplayPage { html, api });
+        }
+
+        info!("Loaded fixture bundle: {} page(s), {} API response(s)", pages.len(), responses.len());
+        Ok(FixtureBundle { pages, responses })
+    }
+
+    fn lookup(&self, target: &str) -> Option<Fixture> {
+        let url = Url::parse("http://replay").ok()?.join(target).ok()?;
+        let key = fixture_key(&url);
+        let param = |name: &str| url.query_pairs().find(|(k, _)| k == name).map(|(_, v)| v.into_owned());
+        let number = param(REPLAY_PAGE_PARAM).and_then(|v| v.parse().ok()).unwrap_or(1);
+
+        // bundles without phases recorded every page under an empty phase
+        for phase in [param(REPLAY_PHASE_PARAM).unwrap_or_default(), String::new()] {
+            if let Some(page) = self.pages.get(&(key.clone(), phase.clone(), number)) {
+                let has_next = self.pages.contains_key(&(key, phase, number + 1));
+                return Some(Fixture::Page { page: page.clone(), has_next, number });
+            }
+        }
+
+        self.responses.get(&key).map(|(status, content_type, body)| Fixture::Response {
+            status: *status,
+            content_type: content_type.clone(),
+            body: body.clone(),
+        })
+    }
+}
+
+/// Removes `<script>` elements so the recorded DOM is not re-rendered by the live site's JavaScript.
+fn strip_scripts(html: &str) -> String {
+    let mut out = String::with_capacity(html.len());
+    let mut rest = html;
+
+    while let Some(start) = rest.find("<script") {
+        out.push_str(&rest[..start]);
+        rest = match rest[start..].find("</script>") {
+            Some(end) => &rest[start + end + "</script>".len()..],
+            None => "",
+        };
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Static pages cannot paginate, so `Další` is turned into a plain navigation to the next recorded page.
+fn next_page_script(next_page: usize) -> String {
+    format!(
+        r#"<script>
+document.addEventListener('click', function (e) {{
+  var a = e.target.closest('a');
+  if (a && a.textContent.trim() === 'Další') {{
+    e.preventDefault();
+    var u = new URL(location.href);
+    u.searchParams.set('{}', '{}');
+    location.href = u.toString();
+  }}
+}}, true);
+</script>"#,
+        REPLAY_PAGE_PARAM, next_page
+    )
+}
+
+/// The recorded DOM is already rendered, so instead of the site's scripts the page just repeats
+/// its data-API requests; they are served from the bundle and show up in the network log as live.
+fn api_requests_script(api: &[String]) -> String {
+    let urls = serde_json::to_string(api).unwrap_or_else(|_| "[]".to_string());
+    format!("<script>{}.forEach(function (u) {{ fetch(u); }});</script>", urls)
+}
+
+fn render_page(page: &ReplayPage, has_next: bool, number: usize) -> String {
+    let mut html = strip_scripts(&page.html);
+    let mut scripts = String::new();
+    if !page.api.is_empty() {
+        scripts.push_str(&api_requests_script(&page.api));
+    }
+    if has_next {
+        scripts.push_str(&next_page_script(number + 1));
+    }
+    match html.rfind("</body>") {
+        Some(pos) => html.insert_str(pos, &scripts),
+        None => html.push_str(&scripts),
+    }
+    html
+}
+
+async fn write_response(stream: &mut TcpStream, status: u16, content_type: &str, body: &[u8]) -> std::io::Result<()> {
+    let head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        if status == 404 { "Not Found" } else { "OK" },
+        content_type,
+        body.len()
+    );
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(body).await?;
+    stream.shutdown().await
+}
+
+async fn handle_connection(mut stream: TcpStream, bundle: Arc<FixtureBundle>) -> std::io::Result<()> {
+    // GET requests only, so the request line is all that matters
+    let mut buf = vec![0u8; 8192];
+    let n = stream.read(&mut buf).await?;
+    let request = String::from_utf8_lossy(&buf[..n]);
+    let target = request.lines().next().and_then(|line| line.split_whitespace().nth(1)).unwrap_or("/");
+
+    match bundle.lookup(target) {
+        Some(Fixture::Page { page, has_next, number }) => {
+            let body = render_page(&page, has_next, number);
+            write_response(&mut stream, 200, "text/html; charset=utf-8", body.as_bytes()).await
+        }
+        Some(Fixture::Response { status, content_type, body }) => {
+            let content_type = content_type.unwrap_or_else(|| "application/octet-stream".to_string());
+            write_response(&mut stream, status, &content_type, &body).await
+        }
+        None => write_response(&mut stream, 404, "text/plain", b"not recorded").await,
+    }
+}
+
+/// Serves the bundle on a random local port in the background; returns the base URL to scrape instead of kodis.cz.
+pub async fn serve_bundle(bundle: FixtureBundle) -> std::io::Result<String> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr: SocketAddr = listener.local_addr()?;
+    let bundle = Arc::new(bundle);
+
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let bundle = Arc::clone(&bundle);
+            tokio::spawn(async move {
+                let _ = handle_connection(stream, bundle).await;
+            });
+        }
+    });
+
+    Ok(format!("http://{}", addr))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(url: &str) -> String {
+        fixture_key(&Url::parse(url).unwrap())
+    }
+
+    #[test]
+    fn fixture_keys_ignore_host_and_replay_parameters() {
+        assert_eq!(key("https://www.kodis.cz/lines/city?tab=MHD+Ostrava"), "/lines/city?tab=MHD Ostrava");
+        assert_eq!(
+            key("http://127.0.0.1:8123/lines/city?tab=MHD+Ostrava&__replay_phase=current&__replay_page=2"),
+            "/lines/city?tab=MHD Ostrava"
+        );
+        assert_eq!(key("https://www.kodis.cz/changes/2410"), "/changes/2410");
+    }
+
+    #[test]
+    fn scripts_are_stripped_with_their_content() {
+        let html = r#"<head><script src="app.js"></script></head><body>a<script>render()</script>b</body>"#;
+        assert_eq!(strip_scripts(html), "<head></head><body>ab</body>");
+        assert_eq!(strip_scripts("<body>a<script>never closed"), "<body>a");
+    }
+
+    #[test]
+    fn recorded_pages_replay_with_their_api_calls() {
+        let dir = std::env::temp_dir().join(format!("canopy-fixtures-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let dir = dir.to_str().unwrap();
+
+        let mut recorder = FixtureRecorder::create(dir, "https://www.kodis.cz").unwrap();
+        let response = CapturedResponse {
+            url: "https://www.kodis.cz/api/links?tab=1".to_string(),
+            status: 200,
+            content_type: Some("application/json".to_string()),
+            body: b"[]".to_vec(),
+        };
+        let url = "https://www.kodis.cz/lines/city?tab=MHD+Ostrava";
+        recorder.record_page(url, "current", 1, "<body>first</body>", &[response]).unwrap();
+        recorder.record_page(url, "current", 2, "<body>second</body>", &[]).unwrap();
+        recorder.finish().unwrap();
+
+        let bundle = FixtureBundle::load(dir).unwrap();
+        let Some(Fixture::Page { page, has_next, number }) = bundle.lookup("/lines/city?tab=MHD+Ostrava&__replay_phase=current") else {
+            panic!("first page not found");
+        };
+        assert!(has_next);
+        let html = render_page(&page, has_next, number);
+        assert!(html.contains(r#"["/api/links?tab=1"].forEach"#), "{}", html);
+        assert!(html.contains("'__replay_page', '2'"), "{}", html);
+
+        let Some(Fixture::Response { status, body, .. }) = bundle.lookup("/api/links?tab=1") else {
+            panic!("API response not found");
+        };
+        assert_eq!((status, body.as_slice()), (200, &b"[]"[..]));
+        assert!(bundle.lookup("/lines/city?tab=MHD+Ostrava&__replay_phase=currentAndFuture").is_none());
+    }
+}
@@ -0,0 +1,153 @@
+use std::fs;
+use serde::{Serialize, Deserialize};
+
+use crate::_13_signing::SigningConfig;
+
+/// Production endpoint, used when no profiles file is present.
+pub const DEFAULT_API_URL: &str = "https://rust-rest-api-endpoints.onrender.com/api/canopy";
+
+/// Where the API key of a profile comes from.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CredentialSource {
+    /// process environment variable
+    Env { var: String },
+    /// whole (trimmed) content of a file, e.g. a mounted secret
+    File { path: String },
+    /// variable from a `.env` file; like `dotenvy::dotenv()`, an already set environment variable wins
+    DotEnv { path: String, var: String },
+}
+
+impl CredentialSource {
+    pub fn resolve(&self) -> Result<String, Box<dyn std::error::Error>> {
+        match self {
+            CredentialSource::Env { var } => std::env::var(var)
+                .map_err(|_| format!("environment variable {} is not set", var).into()),
+            CredentialSource::File { path } => fs::read_to_string(path)
+                .map(|content| content.trim().to_string())
+                .map_err(|e| format!("cannot read credential file {}: {}", path, e).into()),
+            CredentialSource::DotEnv { path, var } => {
+                if let Ok(value) = std::env::var(var) {
+                    return Ok(value);
+                }
+                let entries = dotenvy::from_filename_iter(path)
+                    .map_err(|e| format!("cannot read {}: {}", path, e))?;
+                for entry in entries {
+                    let (key, value) = entry?;
+                    if &key == var {
+                        return Ok(value);
+                    }
+                }
+                Err(format!("{} is neither set nor present in {}", var, path).into())
+            }
+        }
+    }
+
+    /// Human-readable description that never contains the secret itself.
+    pub fn describe(&self) -> String {
+        match self {
+            CredentialSource::Env { var } => format!("env {}", var),
+            CredentialSource::File { path } => format!("file {}", path),
+            CredentialSource::DotEnv { path, var } => format!("{} from {}", var, path),
+        }
+    }
+}
+
+/// Timeouts and retries for uploads; the hosted API can take a minute to wake up from a cold start.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UploadPolicy {
+    pub connect_timeout_secs: u64,
+    pub request_timeout_secs: u64,
+    /// retries after the first attempt, on network errors and 5xx only
+    pub max_retries: u32,
+    /// doubled after every failed attempt
+    pub initial_backoff_ms: u64,
+}
+
+impl Default for UploadPolicy {
+    fn default() -> Self {
+        UploadPolicy {
+            connect_timeout_secs: 15,
+            request_timeout_secs: 120,
+            max_retries: 4,
+            initial_backoff_ms: 2000,
+        }
+    }
+}
+
+/// `Full` PUTs the whole list every time; `Delta` PATCHes only what changed since the last successful upload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UploadMode {
+    #[default]
+    Full,
+    Delta,
+}
+
+/// One upload target: endpoint, header carrying the key, and where the key comes from.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiProfile {
+    pub name: String,
+    pub url: String,
+    pub header_name: String,
+    pub credential: CredentialSource,
+    #[serde(default)]
+    pub upload: UploadPolicy,
+    #[serde(default)]
+    pub mode: UploadMode,
+    /// gzip request bodies (`Content-Encoding: gzip`); a 415 answer falls back to plain JSON
+    #[serde(default)]
+    pub compress: bool,
+    /// HMAC-sign every request (`X-Signature` + `X-Signature-Timestamp`)
+    #[serde(default)]
+    pub signing: Option<SigningConfig>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ApiProfiles {
+    pub default_profile: String,
+    pub profiles: Vec<ApiProfile>,
+}
+
+impl Default for ApiProfiles {
+    /// The behaviour before profiles existed: production URL, `X-API-KEY` from `API_KEY` in `.env`.
+    fn default() -> Self {
+        ApiProfiles {
+            default_profile: "prod".to_string(),
+            profiles: vec![ApiProfile {
+                name: "prod".to_string(),
+                url: DEFAULT_API_URL.to_string(),
+                header_name: "X-API-KEY".to_string(),
+                credential: CredentialSource::DotEnv {
+                    path: ".env".to_string(),
+                    var: "API_KEY".to_string(),
+                },
+                upload: UploadPolicy::default(),
+                mode: UploadMode::Full,
+                compress: false,
+                signing: None,
+            }],
+        }
+    }
+}
+
+impl ApiProfiles {
+    /// Reads the profiles file, or falls back to the built-in production profile when it does not exist.
+    pub fn load(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        match fs::read_to_string(path) {
+            Ok(content) => Ok(serde_json::from_str(&content)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(ApiProfiles::default()),
+            Err(e) => Err(format!("cannot read {}: {}", path, e).into()),
+        }
+    }
+
+    /// `name = None` selects `default_profile`.
+    pub fn select(&self, name: Option<&str>) -> Result<&ApiProfile, Box<dyn std::error::Error>> {
+        let name = name.unwrap_or(&self.default_profile);
+        self.profiles.iter().find(|p| p.name == name).ok_or_else(|| {
+            let known: Vec<&str> = self.profiles.iter().map(|p| p.name.as_str()).collect();
+            format!("unknown API profile '{}' (known: {})", name, known.join(", ")).into()
+        })
+    }
+}
@@ -0,0 +1,107 @@
+use std::collections::BTreeSet;
+use serde::{Serialize, Deserialize};
+
+use crate::_01_http_client::idempotency_key;
+use crate::_02_serialization::LinksPayload;
+
+/// Body of a delta upload, sent as `PATCH` to the profile URL (same headers as the full `PUT`):
+///
+/// ```json
+/// {
+///   "base_hash": "9f2c…",
+///   "added":   ["https://kodis-files.s3.eu-central-1.amazonaws.com/101_2025_12_17_2026_12_12_cd7515410d.pdf"],
+///   "removed": ["https://kodis-files.s3.eu-central-1.amazonaws.com/101_2025_03_16_2025_12_13_d61fb119c2.pdf"]
+/// }
+/// ```
+///
+/// `base_hash` is the canonical hash of the list the delta applies to (see `base_hash`): SHA-256 (hex) of
+/// the compact JSON `{"list":[...]}` with the list sorted and deduplicated, so the server can compute it
+/// from what it stores whatever order the links arrived in. The server should reject the delta (409)
+/// when its stored list has a different hash; the client then falls back to a full `PUT`.
+/// `added` and `removed` are sorted and never overlap. The response has the same `Message1`/`Message2` shape as the `PUT`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LinksDelta {
+    pub base_hash: String,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+impl LinksDelta {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// Order-independent hash of a list, as the server can recompute it from its own copy.
+pub fn base_hash(payload: &LinksPayload) -> Result<String, serde_json::Error> {
+    let links: BTreeSet<&String> = payload.list.iter().collect();
+    let canonical = LinksPayload { list: links.into_iter().cloned().collect() };
+    Ok(idempotency_key(&serde_json::to_vec(&canonical)?))
+}
+
+/// What changed between the last uploaded payload and the current one.
+pub fn compute_delta(previous: &LinksPayload, current: &LinksPayload) -> Result<LinksDelta, serde_json::Error> {
+    let before: BTreeSet<&String> = previous.list.iter().collect();
+    let after: BTreeSet<&String> = current.list.iter().collect();
+
+    Ok(LinksDelta {
+        base_hash: base_hash(previous)?,
+        added: after.difference(&before).map(|s| s.to_string()).collect(),
+        removed: before.difference(&after).map(|s| s.to_string()).collect(),
+    })
+}
+
+/// Last payload the given profile accepted; the base for the next delta.
+pub fn last_uploaded_path(results_dir: &str, profile_name: &str) -> String {
+    format!("{}/last_uploaded_{}.json", results_dir, profile_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn payload(urls: &[&str]) -> LinksPayload {
+        LinksPayload { list: urls.iter().map(|u| u.to_string()).collect() }
+    }
+
+    #[test]
+    fn delta_lists_added_and_removed_links_sorted() {
+        let previous = payload(&["c.pdf", "a.pdf", "b.pdf"]);
+        let delta = compute_delta(&previous, &payload(&["d.pdf", "b.pdf", "a.pdf", "e.pdf"])).unwrap();
+        assert_eq!(delta.added, ["d.pdf", "e.pdf"]);
+        assert_eq!(delta.removed, ["c.pdf"]);
+        assert_eq!(delta.base_hash, base_hash(&previous).unwrap());
+    }
+
+    #[test]
+    fn reordered_or_duplicated_links_are_no_change() {
+        let delta = compute_delta(&payload(&["a.pdf", "b.pdf"]), &payload(&["b.pdf", "a.pdf", "a.pdf"])).unwrap();
+        assert!(delta.is_empty());
+    }
+
+    #[test]
+    fn base_hash_is_the_sorted_deduplicated_list() {
+        let expected = idempotency_key(br#"{"list":["a.pdf","b.pdf"]}"#);
+        assert_eq!(base_hash(&payload(&["b.pdf", "a.pdf", "b.pdf"])).unwrap(), expected);
+    }
+
+    /// The server only keeps the set of links; after each PATCH it must be able to recompute the next base.
+    #[test]
+    fn server_can_follow_put_then_patches() {
+        let mut server: BTreeSet<String> = BTreeSet::new();
+        let server_hash = |server: &BTreeSet<String>| base_hash(&LinksPayload { list: server.iter().cloned().collect() }).unwrap();
+
+        let first = payload(&["c.pdf", "a.pdf"]);
+        server.extend(first.list.iter().cloned());
+
+        let second = payload(&["d.pdf", "a.pdf"]);
+        let delta = compute_delta(&first, &second).unwrap();
+        assert_eq!(delta.base_hash, server_hash(&server));
+        server.retain(|url| !delta.removed.contains(url));
+        server.extend(delta.added);
+
+        let third = payload(&["e.pdf", "d.pdf", "a.pdf"]);
+        let delta = compute_delta(&second, &third).unwrap();
+        assert_eq!(delta.base_hash, server_hash(&server));
+    }
+}
@@ -0,0 +1,87 @@
+use std::collections::BTreeSet;
+
+use crate::_01_http_client::ApiClient;
+use crate::_02_serialization::LinksPayload;
+use crate::{debug, info, warn};
+
+/// Difference between the server's list and ours.
+#[derive(Debug, Default)]
+pub struct Drift {
+    /// we have it, the server does not
+    pub local_only: Vec<String>,
+    /// the server has it, we do not
+    pub server_only: Vec<String>,
+    /// the same URL stored more than once on the server
+    pub server_duplicates: usize,
+}
+
+impl Drift {
+    pub fn is_empty(&self) -> bool {
+        self.local_only.is_empty() && self.server_only.is_empty() && self.server_duplicates == 0
+    }
+}
+
+pub fn compare_lists(server: &LinksPayload, local: &LinksPayload) -> Drift {
+    let server_set: BTreeSet<&String> = server.list.iter().collect();
+    let local_set: BTreeSet<&String> = local.list.iter().collect();
+
+    Drift {
+        local_only: local_set.difference(&server_set).map(|s| s.to_string()).collect(),
+        server_only: server_set.difference(&local_set).map(|s| s.to_string()).collect(),
+        server_duplicates: server.list.len() - server_set.len(),
+    }
+}
+
+fn print_drift(label: &str, drift: &Drift) {
+    if drift.is_empty() {
+        info!("[{}] server and local list match", label);
+        return;
+    }
+
+    info!(
+        "[{}] drift: {} local only, {} server only, {} duplicate(s) on server",
+        label,
+        drift.local_only.len(),
+        drift.server_only.len(),
+        drift.server_duplicates
+    );
+    for url in drift.local_only.iter().take(10) {
+        debug!("  + {}", url);
+    }
+    for url in drift.server_only.iter().take(10) {
+        debug!("  - {}", url);
+    }
+}
+
+/// Reads the server's list and prints how it differs from `local`. A failing GET is only reported,
+/// since the upload itself may still work.
+pub async fn report_drift(client: &ApiClient<'_>, local: &LinksPayload, label: &str) -> Option<Drift> {
+    match client.get_list().await {
+        Ok(server) => {
+            let drift = compare_lists(&server, local);
+            print_drift(label, &drift);
+            Some(drift)
+        }
+        Err(e) => {
+            warn!("[{}] cannot read the server's list: {}", label, e);
+            None
+        }
+    }
+}
+
+/// Confirms the server stored exactly `sent`; any difference is an error.
+pub async fn verify_upload(client: &ApiClient<'_>, sent: &LinksPayload) -> Result<(), Box<dyn std::error::Error>> {
+    let server = client.get_list().await?;
+    let drift = compare_lists(&server, sent);
+    print_drift("after upload", &drift);
+
+    match drift.is_empty() {
+        true => Ok(()),
+        false => Err(format!(
+            "upload verification failed: {} link(s) missing on the server, {} unexpected, {} duplicate(s)",
+            drift.local_only.len(),
+            drift.server_only.len(),
+            drift.server_duplicates
+        ).into()),
+    }
+}
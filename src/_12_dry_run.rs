@@ -0,0 +1,117 @@
+use serde::Serialize;
+
+use crate::_01_http_client::{gzip, idempotency_key};
+use crate::_02_serialization::{LinksPayload, deserialize_from_json, serialize_to_json};
+use crate::_09_api_profiles::{ApiProfile, UploadMode};
+use crate::_10_delta::{LinksDelta, compute_delta, last_uploaded_path};
+use crate::_13_signing::{SIGNATURE_HEADER, TIMESTAMP_HEADER};
+
+/// What a real upload with this profile would do, without doing it.
+#[derive(Debug, Serialize)]
+pub struct UploadPlan {
+    pub profile: String,
+    /// `PUT`, `PATCH`, or `None` when delta mode has nothing to send
+    pub method: Option<&'static str>,
+    pub url: String,
+    /// header values with the credential and the signature redacted
+    pub headers: Vec<(String, String)>,
+    pub link_count: usize,
+    pub body_bytes: usize,
+    /// size on the wire when the profile gzips bodies
+    pub compressed_bytes: Option<usize>,
+    /// change against the last successful upload of this profile (everything is "added" on the first one)
+    pub diff: LinksDelta,
+    /// the exact request body, written to its own file
+    #[serde(skip)]
+    pub body: Option<Vec<u8>>,
+}
+
+/// Where the key comes from and how long it is; no part of the key itself.
+fn redact(profile: &ApiProfile, secret: &str) -> String {
+    format!("*** ({}, {} chars)", profile.credential.describe(), secret.chars().count())
+}
+
+/// Mirrors the decisions of `upload_results`; a missing credential is shown in the plan instead of failing.
+pub fn plan_upload(
+    profile: &ApiProfile,
+    current: &LinksPayload,
+    results_dir: &str,
+) -> Result<UploadPlan, Box<dyn std::error::Error>> {
+    let previous: Option<LinksPayload> = deserialize_from_json(&last_uploaded_path(results_dir, &profile.name)).ok();
+    let empty = LinksPayload { list: Vec::new() };
+    let diff = compute_delta(previous.as_ref().unwrap_or(&empty), current)?;
+
+    let (method, body) = match (profile.mode, &previous) {
+        (UploadMode::Delta, Some(_)) if diff.is_empty() => (None, None),
+        (UploadMode::Delta, Some(_)) => (Some("PATCH"), Some(serde_json::to_vec(&diff)?)),
+        _ => (Some("PUT"), Some(serde_json::to_vec(current)?)),
+    };
+
+    let credential = match profile.credential.resolve() {
+        Ok(key) => redact(profile, &key),
+        Err(e) => format!("<not available: {}>", e),
+    };
+    let mut headers = vec![(profile.header_name.clone(), credential)];
+    if let Some(body) = &body {
+        if let Some(signing) = &profile.signing {
+            // the real upload signs with a fresh timestamp per attempt; the signature itself is never shown
+            let signature = match signing.secret.resolve() {
+                Ok(_) => format!("*** (HMAC-SHA256, secret from {})", signing.secret.describe()),
+                Err(e) => format!("<not available: {}>", e),
+            };
+            headers.push((TIMESTAMP_HEADER.to_string(), chrono::Utc::now().timestamp().to_string()));
+            headers.push((SIGNATURE_HEADER.to_string(), signature));
+        }
+        headers.push(("Idempotency-Key".to_string(), idempotency_key(body)));
+        headers.push(("Content-Type".to_string(), "application/json".to_string()));
+        if profile.compress {
+            headers.push(("Content-Encoding".to_string(), "gzip".to_string()));
+        }
+    }
+    let compressed_bytes = match (&body, profile.compress) {
+        (Some(body), true) => Some(gzip(body)?.len()),
+        _ => None,
+    };
+
+    let plan = UploadPlan {
+        profile: profile.name.clone(),
+        method,
+        url: profile.url.clone(),
+        headers,
+        link_count: current.list.len(),
+        body_bytes: body.as_ref().map_or(0, Vec::len),
+        compressed_bytes,
+        diff,
+        body,
+    };
+    Ok(plan)
+}
+
+/// Writes `dry_run_plan.json` (plan + diff) and `dry_run_body.json` (the exact request body) into `dir`.
+pub fn write_dry_run(plan: &UploadPlan, dir: &str) -> Result<(), Box<dyn std::error::Error>> {
+    serialize_to_json(plan, &format!("{}/dry_run_plan.json", dir))?;
+    if let Some(body) = &plan.body {
+        std::fs::write(format!("{}/dry_run_body.json", dir), body)?;
+    }
+    Ok(())
+}
+
+pub fn print_plan(plan: &UploadPlan) {
+    println!("=== Dry run: nothing is sent ===");
+    match plan.method {
+        Some(method) => match plan.compressed_bytes {
+            Some(compressed) => println!("{} {} (profile {}, {} bytes, {} gzipped)", method, plan.url, plan.profile, plan.body_bytes, compressed),
+            None => println!("{} {} (profile {}, {} bytes)", method, plan.url, plan.profile, plan.body_bytes),
+        },
+        None => println!("No request: nothing changed since the last upload of profile {}", plan.profile),
+    }
+    for (name, value) in &plan.headers {
+        println!("  {}: {}", name, value);
+    }
+    println!(
+        "Links: {} total, +{} / -{} since the last upload",
+        plan.link_count,
+        plan.diff.added.len(),
+        plan.diff.removed.len()
+    );
+}
@@ -0,0 +1,147 @@
+use std::fmt;
+use hmac::{Hmac, Mac};
+use serde::{Serialize, Deserialize};
+use sha2::{Digest, Sha256};
+
+use crate::_09_api_profiles::CredentialSource;
+
+type HmacSha256 = Hmac<Sha256>;
+
+pub const SIGNATURE_HEADER: &str = "X-Signature";
+pub const TIMESTAMP_HEADER: &str = "X-Signature-Timestamp";
+
+/// How far the signed timestamp may be from the receiver's clock; older requests count as replays.
+pub const DEFAULT_MAX_SKEW_SECS: i64 = 300;
+
+/// Optional per-profile signing; the shared secret comes from any credential source.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SigningConfig {
+    pub secret: CredentialSource,
+}
+
+/// What gets signed, one item per line:
+/// `METHOD`, path (with query), Unix timestamp, SHA-256 hex of the body exactly as sent (gzipped if it was).
+pub fn canonical_request(method: &str, path: &str, timestamp: i64, body: &[u8]) -> String {
+    format!(
+        "{}\n{}\n{}\n{:x}",
+        method.to_uppercase(),
+        path,
+        timestamp,
+        Sha256::digest(body)
+    )
+}
+
+fn mac(secret: &[u8], canonical: &str) -> HmacSha256 {
+    // HMAC accepts keys of any length, so this cannot fail
+    let mut mac = HmacSha256::new_from_slice(secret).expect("HMAC accepts any key length");
+    mac.update(canonical.as_bytes());
+    mac
+}
+
+/// HMAC-SHA256 of the canonical request, hex encoded; goes into `X-Signature`.
+pub fn sign_request(secret: &[u8], method: &str, path: &str, timestamp: i64, body: &[u8]) -> String {
+    hex::encode(mac(secret, &canonical_request(method, path, timestamp, body)).finalize().into_bytes())
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SignatureError {
+    MalformedTimestamp,
+    MalformedSignature,
+    /// timestamp outside the allowed window (seconds off the receiver's clock)
+    Expired { skew_secs: i64 },
+    Mismatch,
+}
+
+impl fmt::Display for SignatureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SignatureError::MalformedTimestamp => write!(f, "{} is not a Unix timestamp", TIMESTAMP_HEADER),
+            SignatureError::MalformedSignature => write!(f, "{} is not a hex HMAC", SIGNATURE_HEADER),
+            SignatureError::Expired { skew_secs } => write!(f, "signature timestamp is {}s off", skew_secs),
+            SignatureError::Mismatch => write!(f, "signature does not match"),
+        }
+    }
+}
+
+impl std::error::Error for SignatureError {}
+
+/// A received request as the verifier needs it: raw body bytes and the two signature headers.
+pub struct SignedRequest<'a> {
+    pub method: &'a str,
+    pub path: &'a str,
+    pub body: &'a [u8],
+    pub timestamp_header: &'a str,
+    pub signature_header: &'a str,
+}
+
+/// Receiver side: checks the signature headers against the request. `now` is the receiver's Unix time.
+/// The comparison is constant-time. Within the window a captured request can still be replayed,
+/// so the receiver should also remember the `Idempotency-Key`s it has already applied.
+pub fn verify_request(
+    secret: &[u8],
+    request: &SignedRequest<'_>,
+    now: i64,
+    max_skew_secs: i64,
+) -> Result<(), SignatureError> {
+    let timestamp: i64 = request.timestamp_header.trim().parse().map_err(|_| SignatureError::MalformedTimestamp)?;
+    let skew_secs = (now - timestamp).abs();
+    if skew_secs > max_skew_secs {
+        return Err(SignatureError::Expired { skew_secs });
+    }
+
+    let signature = hex::decode(request.signature_header.trim()).map_err(|_| SignatureError::MalformedSignature)?;
+    mac(secret, &canonical_request(request.method, request.path, timestamp, request.body))
+        .verify_slice(&signature)
+        .map_err(|_| SignatureError::Mismatch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &[u8] = b"shared secret";
+    const NOW: i64 = 1_760_000_000;
+
+    fn request<'a>(body: &'a [u8], timestamp: &'a str, signature: &'a str) -> SignedRequest<'a> {
+        SignedRequest { method: "PUT", path: "/api/canopy", body, timestamp_header: timestamp, signature_header: signature }
+    }
+
+    #[test]
+    fn signed_requests_verify() {
+        let signature = sign_request(SECRET, "put", "/api/canopy", NOW, b"{\"list\":[]}");
+        let timestamp = NOW.to_string();
+        assert_eq!(verify_request(SECRET, &request(b"{\"list\":[]}", &timestamp, &signature), NOW + 10, DEFAULT_MAX_SKEW_SECS), Ok(()));
+    }
+
+    #[test]
+    fn changed_body_or_secret_does_not_verify() {
+        let signature = sign_request(SECRET, "PUT", "/api/canopy", NOW, b"{\"list\":[]}");
+        let timestamp = NOW.to_string();
+        assert_eq!(
+            verify_request(SECRET, &request(b"{\"list\":[\"x\"]}", &timestamp, &signature), NOW, DEFAULT_MAX_SKEW_SECS),
+            Err(SignatureError::Mismatch)
+        );
+        assert_eq!(
+            verify_request(b"other", &request(b"{\"list\":[]}", &timestamp, &signature), NOW, DEFAULT_MAX_SKEW_SECS),
+            Err(SignatureError::Mismatch)
+        );
+    }
+
+    #[test]
+    fn old_or_malformed_headers_are_rejected() {
+        let signature = sign_request(SECRET, "PUT", "/api/canopy", NOW, b"");
+        let timestamp = NOW.to_string();
+        assert_eq!(
+            verify_request(SECRET, &request(b"", &timestamp, &signature), NOW + 301, DEFAULT_MAX_SKEW_SECS),
+            Err(SignatureError::Expired { skew_secs: 301 })
+        );
+        assert_eq!(
+            verify_request(SECRET, &request(b"", "yesterday", &signature), NOW, DEFAULT_MAX_SKEW_SECS),
+            Err(SignatureError::MalformedTimestamp)
+        );
+        assert_eq!(
+            verify_request(SECRET, &request(b"", &timestamp, "not hex"), NOW, DEFAULT_MAX_SKEW_SECS),
+            Err(SignatureError::MalformedSignature)
+        );
+    }
+}
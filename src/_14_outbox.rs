@@ -0,0 +1,184 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde::{Serialize, Deserialize};
+
+use crate::_01_http_client::{ApiClient, put_to_rest_api};
+use crate::_02_serialization::{LinksPayload, deserialize_from_json, serialize_to_json};
+use crate::{info, warn};
+
+/// A payload whose upload failed, waiting for the next run (or `upload --flush-outbox`).
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OutboxEntry {
+    pub run_id: String,
+    pub profile: String,
+    pub queued_at: String,
+    pub attempts: u32,
+    pub last_error: String,
+    pub payload: LinksPayload,
+}
+
+#[derive(Debug, Default)]
+pub struct FlushSummary {
+    pub delivered: usize,
+    pub pending: usize,
+}
+
+/// On-disk queue of failed uploads, one directory per profile and one JSON file per entry named
+/// by the run ID (`%Y%m%d_%H%M%S`), so sorting them gives the order the snapshots were scraped in.
+pub struct Outbox {
+    dir: PathBuf,
+}
+
+impl Outbox {
+    pub fn open(dir: &str) -> std::io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        Ok(Outbox { dir: PathBuf::from(dir) })
+    }
+
+    fn profile_dir(&self, profile: &str) -> PathBuf {
+        self.dir.join(profile)
+    }
+
+    /// Entries of one profile, oldest first.
+    fn entries(&self, profile: &str) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+        let dir = self.profile_dir(profile);
+        if !dir.is_dir() {
+            return Ok(Vec::new());
+        }
+        let mut paths: Vec<PathBuf> = fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .collect();
+        paths.sort();
+        Ok(paths)
+    }
+
+    /// Number of queued snapshots of one profile.
+    pub fn pending(&self, profile: &str) -> Result<usize, Box<dyn std::error::Error>> {
+        Ok(self.entries(profile)?.len())
+    }
+
+    pub fn enqueue(
+        &self,
+        run_id: &str,
+        profile: &str,
+        payload: &LinksPayload,
+        error: &str,
+    ) -> Result<PathBuf, Box<dyn std::error::Error>> {
+        let dir = self.profile_dir(profile);
+        fs::create_dir_all(&dir)?;
+        let path = dir.join(format!("{}.json", run_id));
+        let entry = OutboxEntry {
+            run_id: run_id.to_string(),
+            profile: profile.to_string(),
+            queued_at: chrono::Local::now().to_rfc3339(),
+            attempts: 1,
+            last_error: error.to_string(),
+            payload: LinksPayload { list: payload.list.clone() },
+        };
+        serialize_to_json(&entry, &path_str(&path)?)?;
+        Ok(path)
+    }
+
+    /// A newer snapshot reached the server, so every queued one of that profile is outdated.
+    pub fn drop_stale(&self, profile: &str) -> Result<usize, Box<dyn std::error::Error>> {
+        let paths = self.entries(profile)?;
+        for path in &paths {
+            fs::remove_file(path)?;
+        }
+        Ok(paths.len())
+    }
+
+    /// Retries the profile's entries oldest first and removes the delivered ones.
+    /// Stops at the first failure, so a newer snapshot never overtakes an older one.
+    pub async fn flush(&self, client: &ApiClient<'_>) -> Result<FlushSummary, Box<dyn std::error::Error>> {
+        let profile = &client.profile().name;
+        let paths = self.entries(profile)?;
+        let mut summary = FlushSummary::default();
+
+        for (i, path) in paths.iter().enumerate() {
+            let mut entry: OutboxEntry = deserialize_from_json(&path_str(path)?)?;
+            info!("Outbox: retrying {} (attempt {})", entry.run_id, entry.attempts + 1);
+
+            match put_to_rest_api(client, &entry.payload).await {
+                Ok(_) => {
+                    fs::remove_file(path)?;
+                    summary.delivered += 1;
+                }
+                Err(e) => {
+                    entry.attempts += 1;
+                    entry.last_error = e.to_string();
+                    serialize_to_json(&entry, &path_str(path)?)?;
+                    warn!("Outbox: {} still failing: {}", entry.run_id, e);
+                    summary.pending = paths.len() - i;
+                    break;
+                }
+            }
+        }
+
+        Ok(summary)
+    }
+}
+
+fn path_str(path: &Path) -> Result<String, Box<dyn std::error::Error>> {
+    path.to_str()
+        .map(str::to_string)
+        .ok_or_else(|| format!("non UTF-8 path {}", path.display()).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outbox(name: &str) -> Outbox {
+        let dir = std::env::temp_dir().join(format!("canopy-outbox-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        Outbox::open(dir.to_str().unwrap()).unwrap()
+    }
+
+    fn payload(url: &str) -> LinksPayload {
+        LinksPayload { list: vec![url.to_string()] }
+    }
+
+    fn queued_runs(outbox: &Outbox, profile: &str) -> Vec<String> {
+        outbox
+            .entries(profile)
+            .unwrap()
+            .iter()
+            .map(|path| deserialize_from_json::<OutboxEntry>(path.to_str().unwrap()).unwrap().run_id)
+            .collect()
+    }
+
+    #[test]
+    fn entries_come_back_oldest_first() {
+        let outbox = outbox("order");
+        for run_id in ["20260102_080000", "20260101_080000", "20260101_200000"] {
+            outbox.enqueue(run_id, "prod", &payload(run_id), "timeout").unwrap();
+        }
+        assert_eq!(queued_runs(&outbox, "prod"), ["20260101_080000", "20260101_200000", "20260102_080000"]);
+        assert_eq!(outbox.pending("test").unwrap(), 0);
+    }
+
+    #[test]
+    fn profiles_with_overlapping_names_are_kept_apart() {
+        let outbox = outbox("overlap");
+        outbox.enqueue("20260101_080000", "prod", &payload("a.pdf"), "timeout").unwrap();
+        outbox.enqueue("20260101_080000", "pre_prod", &payload("b.pdf"), "timeout").unwrap();
+        outbox.enqueue("20260102_080000", "pre_prod", &payload("c.pdf"), "timeout").unwrap();
+
+        assert_eq!(outbox.pending("prod").unwrap(), 1);
+        assert_eq!(outbox.pending("pre_prod").unwrap(), 2);
+    }
+
+    #[test]
+    fn drop_stale_removes_only_the_profile_given() {
+        let outbox = outbox("stale");
+        outbox.enqueue("20260101_080000", "prod", &payload("a.pdf"), "timeout").unwrap();
+        outbox.enqueue("20260102_080000", "prod", &payload("b.pdf"), "timeout").unwrap();
+        outbox.enqueue("20260101_080000", "pre_prod", &payload("c.pdf"), "timeout").unwrap();
+
+        assert_eq!(outbox.drop_stale("prod").unwrap(), 2);
+        assert_eq!(outbox.pending("prod").unwrap(), 0);
+        assert_eq!(queued_runs(&outbox, "pre_prod"), ["20260101_080000"]);
+    }
+}
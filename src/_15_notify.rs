@@ -0,0 +1,273 @@
+use std::collections::HashMap;
+use std::fs;
+use chrono::NaiveDate;
+use futures::future::{FutureExt, LocalBoxFuture};
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use lettre::transport::smtp::authentication::Credentials;
+use serde::{Serialize, Deserialize};
+use serde_json::json;
+
+use crate::_02_serialization::LinksPayload;
+use crate::_05_links::{TimetableFile, parse_timetable_url};
+use crate::_09_api_profiles::CredentialSource;
+use crate::_10_delta::LinksDelta;
+use crate::{info, warn};
+
+/// ===================== Notification =====================
+#[derive(Debug, Clone, Serialize)]
+pub struct ExpiringTimetable {
+    pub line: String,
+    pub valid_to: NaiveDate,
+    pub url: String,
+    /// a later timetable of the same line is already published
+    pub has_successor: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Notification {
+    pub run_id: String,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub expiring: Vec<ExpiringTimetable>,
+    /// set when the scrape itself failed
+    pub error: Option<String>,
+}
+
+impl Notification {
+    pub fn changes(run_id: &str, diff: &LinksDelta, expiring: Vec<ExpiringTimetable>) -> Self {
+        Notification {
+            run_id: run_id.to_string(),
+            added: diff.added.clone(),
+            removed: diff.removed.clone(),
+            expiring,
+            error: None,
+        }
+    }
+
+    pub fn failure(run_id: &str, error: &str) -> Self {
+        Notification {
+            run_id: run_id.to_string(),
+            added: Vec::new(),
+            removed: Vec::new(),
+            expiring: Vec::new(),
+            error: Some(error.to_string()),
+        }
+    }
+
+    pub fn subject(&self) -> String {
+        match &self.error {
+            Some(_) => format!("KODIS scraper run {} failed", self.run_id),
+            None => format!("KODIS timetables: {} new, {} removed", self.added.len(), self.removed.len()),
+        }
+    }
+
+    /// Plain-text summary shared by Slack and e-mail.
+    pub fn text(&self) -> String {
+        let mut lines = vec![self.subject()];
+        if let Some(error) = &self.error {
+            lines.push(format!("Error: {}", error));
+        }
+        lines.extend(self.added.iter().map(|url| format!("+ {}", url)));
+        lines.extend(self.removed.iter().map(|url| format!("- {}", url)));
+        if !self.expiring.is_empty() {
+            lines.push("Expiring soon:".to_string());
+            lines.extend(self.expiring.iter().map(|t| {
+                let successor = if t.has_successor { "successor published" } else { "NO successor yet" };
+                format!("  {} until {} ({})", t.line, t.valid_to, successor)
+            }));
+        }
+        lines.join("\n")
+    }
+}
+
+/// Timetables whose validity ends within `within_days` of `today`.
+pub fn expiring_timetables(payload: &LinksPayload, today: NaiveDate, within_days: i64) -> Vec<ExpiringTimetable> {
+    let files: Vec<(String, TimetableFile)> = payload
+        .list
+        .iter()
+        .filter_map(|url| parse_timetable_url(url).map(|file| (url.clone(), file)))
+        .collect();
+
+    let mut latest_end: HashMap<&str, NaiveDate> = HashMap::new();
+    for (_, file) in &files {
+        let end = latest_end.entry(file.line.as_str()).or_insert(file.valid_to);
+        *end = (*end).max(file.valid_to);
+    }
+
+    let horizon = today + chrono::Duration::days(within_days);
+    let mut expiring: Vec<ExpiringTimetable> = files
+        .iter()
+        .filter(|(_, file)| file.valid_to >= today && file.valid_to <= horizon)
+        .map(|(url, file)| ExpiringTimetable {
+            line: file.line.clone(),
+            valid_to: file.valid_to,
+            url: url.clone(),
+            has_successor: latest_end.get(file.line.as_str()).is_some_and(|end| *end > file.valid_to),
+        })
+        .collect();
+    expiring.sort_by(|a, b| (a.valid_to, &a.line).cmp(&(b.valid_to, &b.line)));
+    expiring
+}
+
+/// ===================== Notifiers =====================
+/// A notification target. Boxed futures keep the trait object-safe, so sinks can be mixed in one list.
+pub trait Notifier {
+    fn name(&self) -> String;
+    fn notify<'a>(&'a self, notification: &'a Notification) -> LocalBoxFuture<'a, Result<(), Box<dyn std::error::Error>>>;
+}
+
+async fn post_json(url: &str, body: &serde_json::Value) -> Result<(), Box<dyn std::error::Error>> {
+    let response = reqwest::Client::new().post(url).json(body).send().await?;
+    match response.status().is_success() {
+        true => Ok(()),
+        false => Err(format!("{} answered {}", url, response.status()).into()),
+    }
+}
+
+/// POSTs the whole `Notification` as JSON.
+pub struct JsonWebhook {
+    pub url: String,
+}
+
+impl Notifier for JsonWebhook {
+    fn name(&self) -> String {
+        format!("webhook {}", self.url)
+    }
+
+    fn notify<'a>(&'a self, notification: &'a Notification) -> LocalBoxFuture<'a, Result<(), Box<dyn std::error::Error>>> {
+        async move { post_json(&self.url, &serde_json::to_value(notification)?).await }.boxed_local()
+    }
+}
+
+/// Slack-compatible incoming webhook (`{"text": ...}`), also understood by Mattermost and Rocket.Chat.
+pub struct SlackWebhook {
+    pub url: String,
+}
+
+impl Notifier for SlackWebhook {
+    fn name(&self) -> String {
+        "slack webhook".to_string()
+    }
+
+    fn notify<'a>(&'a self, notification: &'a Notification) -> LocalBoxFuture<'a, Result<(), Box<dyn std::error::Error>>> {
+        async move { post_json(&self.url, &json!({ "text": notification.text() })).await }.boxed_local()
+    }
+}
+
+/// Plain-text e-mail over SMTP. Without `starttls` the connection is unencrypted, which is what
+/// local SMTP sinks (MailHog, Mailpit, `python -m aiosmtpd`) expect.
+pub struct EmailNotifier {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: String,
+    to: Vec<String>,
+}
+
+impl Notifier for EmailNotifier {
+    fn name(&self) -> String {
+        format!("e-mail to {}", self.to.join(", "))
+    }
+
+    fn notify<'a>(&'a self, notification: &'a Notification) -> LocalBoxFuture<'a, Result<(), Box<dyn std::error::Error>>> {
+        async move {
+            let mut message = Message::builder().from(self.from.parse()?).subject(notification.subject());
+            for to in &self.to {
+                message = message.to(to.parse()?);
+            }
+            self.transport.send(message.body(notification.text())?).await?;
+            Ok(())
+        }
+        .boxed_local()
+    }
+}
+
+/// ===================== Configuration =====================
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum NotifierConfig {
+    JsonWebhook {
+        url: String,
+    },
+    Slack {
+        url: String,
+    },
+    Email {
+        smtp_host: String,
+        smtp_port: u16,
+        #[serde(default)]
+        starttls: bool,
+        from: String,
+        to: Vec<String>,
+        #[serde(default)]
+        username: Option<String>,
+        #[serde(default)]
+        password: Option<CredentialSource>,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NotifyConfig {
+    #[serde(default = "default_expiring_within_days")]
+    pub expiring_within_days: i64,
+    pub notifiers: Vec<NotifierConfig>,
+}
+
+fn default_expiring_within_days() -> i64 {
+    14
+}
+
+impl Default for NotifyConfig {
+    fn default() -> Self {
+        NotifyConfig {
+            expiring_within_days: default_expiring_within_days(),
+            notifiers: Vec::new(),
+        }
+    }
+}
+
+impl NotifyConfig {
+    /// No file means no notifications.
+    pub fn load(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        match fs::read_to_string(path) {
+            Ok(content) => Ok(serde_json::from_str(&content)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(NotifyConfig::default()),
+            Err(e) => Err(format!("cannot read {}: {}", path, e).into()),
+        }
+    }
+
+    pub fn build(&self) -> Result<Vec<Box<dyn Notifier>>, Box<dyn std::error::Error>> {
+        self.notifiers.iter().map(build_notifier).collect()
+    }
+}
+
+fn build_notifier(config: &NotifierConfig) -> Result<Box<dyn Notifier>, Box<dyn std::error::Error>> {
+    match config {
+        NotifierConfig::JsonWebhook { url } => Ok(Box::new(JsonWebhook { url: url.clone() })),
+        NotifierConfig::Slack { url } => Ok(Box::new(SlackWebhook { url: url.clone() })),
+        NotifierConfig::Email { smtp_host, smtp_port, starttls, from, to, username, password } => {
+            let mut builder = match starttls {
+                true => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(smtp_host)?,
+                false => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(smtp_host),
+            }
+            .port(*smtp_port);
+            if let (Some(username), Some(password)) = (username, password) {
+                builder = builder.credentials(Credentials::new(username.clone(), password.resolve()?));
+            }
+
+            Ok(Box::new(EmailNotifier {
+                transport: builder.build(),
+                from: from.clone(),
+                to: to.clone(),
+            }))
+        }
+    }
+}
+
+/// Sends to every sink; a broken sink is reported but never fails the run.
+pub async fn notify_all(notifiers: &[Box<dyn Notifier>], notification: &Notification) {
+    for notifier in notifiers {
+        match notifier.notify(notification).await {
+            Ok(()) => info!("Notified {}", notifier.name()),
+            Err(e) => warn!("Notifying {} failed: {}", notifier.name(), e),
+        }
+    }
+}
@@ -0,0 +1,283 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use futures::stream::{self, StreamExt};
+use reqwest::{StatusCode, header};
+use serde::{Serialize, Deserialize};
+use tokio::io::AsyncWriteExt;
+
+use crate::_05_links::parse_timetable_url;
+
+/// Response validators of a mirrored file, stored next to it as `<file>.meta.json`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MirrorMeta {
+    pub url: String,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    pub content_length: Option<u64>,
+    pub downloaded_at: String,
+}
+
+#[derive(Debug)]
+pub enum MirrorOutcome {
+    Downloaded,
+    Resumed,
+    /// 304: the local copy is still current
+    Unchanged,
+}
+
+#[derive(Debug, Default)]
+pub struct MirrorSummary {
+    pub downloaded: usize,
+    pub resumed: usize,
+    pub unchanged: usize,
+    pub failed: Vec<(String, String)>,
+}
+
+/// `{root}/{line}/{valid_from}_{valid_to}/{file name}`; names that do not parse go to `{root}/_other/`.
+pub fn mirror_path(root: &str, url: &str) -> PathBuf {
+    let file_name = url.rsplit('/').next().unwrap_or(url);
+    match parse_timetable_url(url) {
+        Some(file) => Path::new(root)
+            .join(&file.line)
+            .join(format!("{}_{}", file.valid_from, file.valid_to))
+            .join(file_name),
+        None => Path::new(root).join("_other").join(file_name),
+    }
+}
+
+/// `path` with `suffix` appended to the whole file name (`a.pdf` + `.part` → `a.pdf.part`).
+pub(crate) fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+pub fn meta_path(path: &Path) -> PathBuf {
+    with_suffix(path, ".meta.json")
+}
+
+fn read_meta(path: &Path) -> Option<MirrorMeta> {
+    serde_json::from_str(&std::fs::read_to_string(meta_path(path)).ok()?).ok()
+}
+
+fn header_string(response: &reqwest::Response, name: header::HeaderName) -> Option<String> {
+    response.headers().get(name).and_then(|v| v.to_str().ok()).map(str::to_string)
+}
+
+/// Total size from the `Content-Range: bytes */N` of a `416` response.
+fn unsatisfied_range_length(response: &reqwest::Response) -> Option<u64> {
+    header_string(response, header::CONTENT_RANGE)?.strip_prefix("bytes */")?.trim().parse().ok()
+}
+
+/// Downloads one PDF. A complete copy is revalidated with `If-None-Match` / `If-Modified-Since`;
+/// a `.part` file left by an interrupted run is continued with `Range` + `If-Range` (the stored ETag, or
+/// `Last-Modified` without one), so a changed file restarts. Without either validator the download restarts.
+async fn mirror_one(client: &reqwest::Client, root: &str, url: &str) -> Result<MirrorOutcome, Box<dyn std::error::Error>> {
+    let path = mirror_path(root, url);
+    let part = with_suffix(&path, ".part");
+    if let Some(dir) = path.parent() {
+        tokio::fs::create_dir_all(dir).await?;
+    }
+
+    let meta = read_meta(&path);
+    let if_range = meta.as_ref().and_then(|m| m.etag.as_ref().or(m.last_modified.as_ref()));
+    let mut part_len = tokio::fs::metadata(&part).await.map(|m| m.len()).unwrap_or(0);
+    if part_len > 0 && if_range.is_none() {
+        // appending to bytes of an unknown version could splice two different files
+        tokio::fs::remove_file(&part).await?;
+        part_len = 0;
+    }
+    let have_complete = path.exists();
+
+    let mut request = client.get(url);
+    if let Some(if_range) = if_range.filter(|_| part_len > 0) {
+        request = request.header(header::RANGE, format!("bytes={}-", part_len)).header(header::IF_RANGE, if_range);
+    } else if have_complete && let Some(meta) = &meta {
+        if let Some(etag) = &meta.etag {
+            request = request.header(header::IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &meta.last_modified {
+            request = request.header(header::IF_MODIFIED_SINCE, last_modified);
+        }
+    }
+
+    let mut response = request.send().await?;
+    let status = response.status();
+    if status == StatusCode::NOT_MODIFIED {
+        return Ok(MirrorOutcome::Unchanged);
+    }
+    if status == StatusCode::RANGE_NOT_SATISFIABLE && part_len > 0 {
+        let total = unsatisfied_range_length(&response);
+        if total == Some(part_len) {
+            // the previous run got every byte but stopped before the rename
+            tokio::fs::rename(&part, &path).await?;
+            return Ok(MirrorOutcome::Resumed);
+        }
+        tokio::fs::remove_file(&part).await?;
+        return Err(match total {
+            Some(total) => format!("partial copy has {} bytes but the file has {}; discarded, the next run downloads it again", part_len, total),
+            None => format!("HTTP {} without the file size; partial copy discarded", status),
+        }
+        .into());
+    }
+    if !status.is_success() {
+        return Err(format!("HTTP {}", status).into());
+    }
+
+    let resumed = status == StatusCode::PARTIAL_CONTENT;
+    let new_meta = MirrorMeta {
+        url: url.to_string(),
+        etag: header_string(&response, header::ETAG),
+        last_modified: header_string(&response, header::LAST_MODIFIED),
+        content_length: match resumed {
+            true => response.content_length().map(|len| len + part_len),
+            false => response.content_length(),
+        },
+        downloaded_at: chrono::Local::now().to_rfc3339(),
+    };
+    // validators are saved before the body, so an interrupted download can be resumed with If-Range
+    tokio::fs::write(meta_path(&path), serde_json::to_string_pretty(&new_meta)?).await?;
+
+    let mut file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(resumed)
+        .truncate(!resumed)
+        .open(&part)
+        .await?;
+    while let Some(chunk) = response.chunk().await? {
+        file.write_all(&chunk).await?;
+    }
+    file.flush().await?;
+    drop(file);
+
+    tokio::fs::rename(&part, &path).await?;
+    Ok(if resumed { MirrorOutcome::Resumed } else { MirrorOutcome::Downloaded })
+}
+
+/// A stalled download fails after this and is resumed on the next run.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(15);
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Mirrors every PDF URL into `root`, at most `max_concurrency` downloads at a time.
+pub async fn mirror_pdfs(urls: &[String], root: &str, max_concurrency: usize) -> Result<MirrorSummary, Box<dyn std::error::Error>> {
+    let client = reqwest::Client::builder()
+        .connect_timeout(CONNECT_TIMEOUT)
+        .timeout(DOWNLOAD_TIMEOUT)
+        .build()?;
+    let client = &client;
+
+    let results: Vec<(String, Result<MirrorOutcome, String>)> = stream::iter(urls.iter().filter(|url| url.ends_with(".pdf")))
+        .map(|url| async move {
+            let result = mirror_one(client, root, url).await.map_err(|e| e.to_string());
+            (url.clone(), result)
+        })
+        .buffer_unordered(max_concurrency.max(1))
+        .collect()
+        .await;
+
+    let mut summary = MirrorSummary::default();
+    for (url, result) in results {
+        match result {
+            Ok(MirrorOutcome::Downloaded) => summary.downloaded += 1,
+            Ok(MirrorOutcome::Resumed) => summary.resumed += 1,
+            Ok(MirrorOutcome::Unchanged) => summary.unchanged += 1,
+            Err(e) => summary.failed.push((url, e)),
+        }
+    }
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use tokio::io::AsyncReadExt;
+    use tokio::net::TcpListener;
+
+    const URL_PATH: &str = "/101_2025_12_17_2026_12_12_cd7515410d.pdf";
+
+    /// Answers every request with `response` and keeps the request heads it saw.
+    async fn start_stub(response: &'static str) -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}{}", listener.local_addr().unwrap(), URL_PATH);
+        let heads = Arc::new(Mutex::new(Vec::new()));
+        let seen = Arc::clone(&heads);
+
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut buf = Vec::new();
+                let mut chunk = [0u8; 4096];
+                while !buf.windows(4).any(|w| w == b"\r\n\r\n") {
+                    let n = stream.read(&mut chunk).await.unwrap();
+                    buf.extend_from_slice(&chunk[..n]);
+                }
+                seen.lock().unwrap().push(String::from_utf8_lossy(&buf).to_lowercase());
+                stream.write_all(response.as_bytes()).await.unwrap();
+                let _ = stream.shutdown().await;
+            }
+        });
+        (url, heads)
+    }
+
+    /// Mirror root with a `.part` file of `part` bytes and, optionally, the validators of the interrupted run.
+    fn root_with_part(name: &str, url: &str, part: &[u8], meta: Option<MirrorMeta>) -> String {
+        let root = std::env::temp_dir().join(format!("canopy-mirror-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let root = root.to_string_lossy().to_string();
+        let path = mirror_path(&root, url);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(with_suffix(&path, ".part"), part).unwrap();
+        if let Some(meta) = meta {
+            std::fs::write(meta_path(&path), serde_json::to_string(&meta).unwrap()).unwrap();
+        }
+        root
+    }
+
+    fn last_modified_only(url: &str) -> MirrorMeta {
+        MirrorMeta {
+            url: url.to_string(),
+            last_modified: Some("Wed, 17 Dec 2025 08:00:00 GMT".to_string()),
+            ..MirrorMeta::default()
+        }
+    }
+
+    const UNSATISFIABLE_4: &str =
+        "HTTP/1.1 416 Range Not Satisfiable\r\nContent-Range: bytes */4\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+
+    #[tokio::test]
+    async fn a_complete_part_is_kept_on_416() {
+        let (url, heads) = start_stub(UNSATISFIABLE_4).await;
+        let root = root_with_part("complete", &url, b"%PDF", Some(last_modified_only(&url)));
+
+        let outcome = mirror_one(&reqwest::Client::new(), &root, &url).await.unwrap();
+        assert!(matches!(outcome, MirrorOutcome::Resumed));
+        assert_eq!(std::fs::read(mirror_path(&root, &url)).unwrap(), b"%PDF");
+        // without an ETag the resume is conditional on Last-Modified
+        let head = heads.lock().unwrap()[0].clone();
+        assert!(head.contains("range: bytes=4-"));
+        assert!(head.contains("if-range: wed, 17 dec 2025 08:00:00 gmt"));
+    }
+
+    #[tokio::test]
+    async fn a_part_of_another_size_is_discarded_on_416() {
+        let (url, _) = start_stub(UNSATISFIABLE_4).await;
+        let root = root_with_part("mismatch", &url, b"%PDF-1", Some(last_modified_only(&url)));
+
+        assert!(mirror_one(&reqwest::Client::new(), &root, &url).await.is_err());
+        let path = mirror_path(&root, &url);
+        assert!(!path.exists());
+        assert!(!with_suffix(&path, ".part").exists());
+    }
+
+    #[tokio::test]
+    async fn a_part_without_validators_restarts_from_zero() {
+        let (url, heads) = start_stub("HTTP/1.1 200 OK\r\nContent-Length: 8\r\nConnection: close\r\n\r\n%PDF-new").await;
+        let root = root_with_part("restart", &url, b"%PDF", None);
+
+        let outcome = mirror_one(&reqwest::Client::new(), &root, &url).await.unwrap();
+        assert!(matches!(outcome, MirrorOutcome::Downloaded));
+        assert_eq!(std::fs::read(mirror_path(&root, &url)).unwrap(), b"%PDF-new");
+        assert!(!heads.lock().unwrap()[0].contains("range:"));
+    }
+}
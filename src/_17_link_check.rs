@@ -0,0 +1,148 @@
+use std::time::Duration;
+use futures::stream::{self, StreamExt};
+use reqwest::{StatusCode, header};
+use serde::{Serialize, Deserialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LinkProblem {
+    /// 404 / 410, or 403: S3 answers 403 for missing objects when the bucket cannot be listed
+    Missing,
+    /// answered, but not with a PDF (typically an HTML error page)
+    NotPdf,
+    /// any other non-2xx status
+    HttpError,
+    /// no HTTP answer at all; usually transient, so never treated as dead
+    Unreachable,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LinkCheck {
+    pub url: String,
+    /// `HEAD`, or `GET` when the ranged GET fallback was needed
+    pub method: String,
+    pub status: Option<u16>,
+    pub content_type: Option<String>,
+    pub size: Option<u64>,
+    pub problem: Option<LinkProblem>,
+    pub error: Option<String>,
+}
+
+impl LinkCheck {
+    /// Dead links are left out of the upload with `--exclude-dead-links`.
+    pub fn is_dead(&self) -> bool {
+        matches!(self.problem, Some(LinkProblem::Missing | LinkProblem::NotPdf))
+    }
+}
+
+fn classify(status: StatusCode, content_type: Option<&str>) -> Option<LinkProblem> {
+    match status {
+        StatusCode::NOT_FOUND | StatusCode::GONE | StatusCode::FORBIDDEN => Some(LinkProblem::Missing),
+        s if !s.is_success() => Some(LinkProblem::HttpError),
+        _ => match content_type {
+            Some(ct) if ct.starts_with("application/pdf") || ct.starts_with("binary/octet-stream") || ct.starts_with("application/octet-stream") => None,
+            _ => Some(LinkProblem::NotPdf),
+        },
+    }
+}
+
+/// Total size from `Content-Range: bytes 0-0/12345` of a ranged GET.
+fn size_from_content_range(response: &reqwest::Response) -> Option<u64> {
+    let range = response.headers().get(header::CONTENT_RANGE)?.to_str().ok()?;
+    range.rsplit('/').next()?.parse().ok()
+}
+
+fn check_from_response(url: &str, method: &str, response: &reqwest::Response) -> LinkCheck {
+    let status = response.status();
+    let content_type = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    let size = match status {
+        StatusCode::PARTIAL_CONTENT => size_from_content_range(response),
+        _ => response.content_length(),
+    };
+
+    LinkCheck {
+        url: url.to_string(),
+        method: method.to_string(),
+        status: Some(status.as_u16()),
+        problem: classify(status, content_type.as_deref()),
+        content_type,
+        size,
+        error: None,
+    }
+}
+
+/// HEAD first; servers that refuse HEAD (405/501, or a network error) get a GET for the first byte only.
+async fn check_link(client: &reqwest::Client, url: &str) -> LinkCheck {
+    if let Ok(response) = client.head(url).send().await
+        && !matches!(response.status(), StatusCode::METHOD_NOT_ALLOWED | StatusCode::NOT_IMPLEMENTED)
+    {
+        return check_from_response(url, "HEAD", &response);
+    }
+
+    match client.get(url).header(header::RANGE, "bytes=0-0").send().await {
+        Ok(response) => check_from_response(url, "GET", &response),
+        Err(e) => LinkCheck {
+            url: url.to_string(),
+            method: "GET".to_string(),
+            status: None,
+            content_type: None,
+            size: None,
+            problem: Some(LinkProblem::Unreachable),
+            error: Some(e.to_string()),
+        },
+    }
+}
+
+/// HEAD requests get no body, so anything slower than this is a stalled connection.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const HEAD_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Client for HEAD-only checks; a URL that stalls is reported as unreachable instead of hanging the run.
+pub fn head_client() -> reqwest::Result<reqwest::Client> {
+    reqwest::Client::builder()
+        .connect_timeout(CONNECT_TIMEOUT)
+        .timeout(HEAD_TIMEOUT)
+        .build()
+}
+
+/// Checks every URL, at most `max_concurrency` at a time; results are in the order of `urls`.
+pub async fn check_links(urls: &[String], max_concurrency: usize) -> reqwest::Result<Vec<LinkCheck>> {
+    let client = head_client()?;
+    let client = &client;
+
+    Ok(stream::iter(urls)
+        .map(|url| check_link(client, url))
+        .buffered(max_concurrency.max(1))
+        .collect()
+        .await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_objects_are_missing_whatever_the_content_type() {
+        for status in [StatusCode::NOT_FOUND, StatusCode::GONE, StatusCode::FORBIDDEN] {
+            assert_eq!(classify(status, Some("application/xml")), Some(LinkProblem::Missing));
+        }
+    }
+
+    #[test]
+    fn other_error_statuses_are_http_errors() {
+        assert_eq!(classify(StatusCode::INTERNAL_SERVER_ERROR, None), Some(LinkProblem::HttpError));
+        assert_eq!(classify(StatusCode::TOO_MANY_REQUESTS, Some("application/pdf")), Some(LinkProblem::HttpError));
+    }
+
+    #[test]
+    fn success_needs_a_pdf_content_type() {
+        assert_eq!(classify(StatusCode::OK, Some("application/pdf")), None);
+        assert_eq!(classify(StatusCode::PARTIAL_CONTENT, Some("binary/octet-stream")), None);
+        assert_eq!(classify(StatusCode::OK, Some("application/octet-stream")), None);
+        assert_eq!(classify(StatusCode::OK, Some("text/html; charset=utf-8")), Some(LinkProblem::NotPdf));
+        assert_eq!(classify(StatusCode::OK, None), Some(LinkProblem::NotPdf));
+    }
+}
@@ -0,0 +1,179 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde::{Serialize, Deserialize};
+
+use crate::_05_links::parse_timetable_url;
+use crate::_16_mirror::with_suffix;
+
+/// Parts of a KODIS line timetable recognised in its text layer. The split is heuristic;
+/// `text` always holds the full extracted text, so nothing is lost when a part is not recognised.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct TimetableText {
+    pub file: String,
+    /// e.g. `101` from the file name, when it parses
+    pub line: Option<String>,
+    /// first line mentioning the line number, e.g. `Linka 101`
+    pub line_header: Option<String>,
+    /// `from - to` description of the route
+    pub route: Option<String>,
+    /// stop names in the order they appear
+    pub stops: Vec<String>,
+    /// day-type and service notes (`jede v pracovních dnech`, `nejede 24.12.`, ...)
+    pub footnotes: Vec<String>,
+    pub text: String,
+}
+
+#[derive(Debug, Default)]
+pub struct ExtractSummary {
+    pub extracted: usize,
+    pub up_to_date: usize,
+    pub failed: Vec<(String, String)>,
+}
+
+/// Words that only occur in the footnotes explaining when a trip runs.
+const FOOTNOTE_MARKERS: &[&str] = &[
+    "jede", "nejede", "pracovních dnech", "sobot", "neděl", "svátk", "platí", "zastávka na znamení", "spoj ",
+];
+
+/// `5:07`, `15:40`
+fn has_times(line: &str) -> bool {
+    line.split_whitespace().any(|token| {
+        token.split_once(':').is_some_and(|(h, m)| {
+            !h.is_empty() && h.len() <= 2 && m.len() == 2 && h.chars().chain(m.chars()).all(|c| c.is_ascii_digit())
+        })
+    })
+}
+
+/// A row with a marker and no departure times; stop names can contain markers too (`Sobotín`).
+pub fn is_footnote(line: &str) -> bool {
+    let lower = line.to_lowercase();
+    FOOTNOTE_MARKERS.iter().any(|marker| lower.contains(marker)) && !has_times(line)
+}
+
+/// Stop rows start with the stop name and continue with kilometres, platforms and times;
+/// the name is everything before the first digit or table separator.
+pub fn stop_name(line: &str) -> Option<String> {
+    if !line.chars().next()?.is_uppercase() {
+        return None;
+    }
+    let end = line.find(|c: char| c.is_ascii_digit() || c == '|').unwrap_or(line.len());
+    let name = line[..end].trim().trim_end_matches(['-', '–', '.', ',']).trim();
+
+    match name.chars().count() >= 3 && name.chars().any(char::is_lowercase) {
+        true => Some(name.to_string()),
+        false => None,
+    }
+}
+
+fn is_route(line: &str) -> bool {
+    (line.contains(" - ") || line.contains(" – ")) && !line.contains(':')
+}
+
+pub fn split_sections(file: &str, line: Option<String>, text: String) -> TimetableText {
+    let rows: Vec<&str> = text.lines().map(str::trim).filter(|l| !l.is_empty()).collect();
+
+    let line_header = line
+        .as_ref()
+        .and_then(|number| rows.iter().find(|row| row.split_whitespace().any(|word| word == number)))
+        .map(|row| row.to_string());
+    let route = rows.iter().find(|row| is_route(row)).map(|row| row.to_string());
+    let footnotes: Vec<String> = rows.iter().filter(|row| is_footnote(row)).map(|row| row.to_string()).collect();
+
+    let mut stops: Vec<String> = Vec::new();
+    for row in &rows {
+        if is_footnote(row) || Some(row.to_string()) == line_header || Some(row.to_string()) == route {
+            continue;
+        }
+        if let Some(name) = stop_name(row)
+            && !stops.contains(&name)
+        {
+            stops.push(name);
+        }
+    }
+
+    TimetableText {
+        file: file.to_string(),
+        line,
+        line_header,
+        route,
+        stops,
+        footnotes,
+        text,
+    }
+}
+
+/// pdf-extract panics on some malformed PDFs; that must only fail this one file.
+fn extract_text(path: &Path) -> Result<String, String> {
+    let path = path.to_path_buf();
+    std::panic::catch_unwind(move || pdf_extract::extract_text(&path))
+        .map_err(|_| "PDF parser panicked".to_string())?
+        .map_err(|e| e.to_string())
+}
+
+fn sections_path(pdf: &Path) -> PathBuf {
+    with_suffix(pdf, ".sections.json")
+}
+
+/// Raw text next to the PDF (`.txt`) and its recognised parts (`.pdf.sections.json`).
+pub fn extract_pdf(pdf: &Path) -> Result<TimetableText, String> {
+    let text = extract_text(pdf)?;
+    let file_name = pdf.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+    let line = parse_timetable_url(file_name).map(|f| f.line);
+    let sections = split_sections(file_name, line, text);
+
+    fs::write(pdf.with_extension("txt"), &sections.text).map_err(|e| e.to_string())?;
+    let json = serde_json::to_string_pretty(&sections).map_err(|e| e.to_string())?;
+    fs::write(sections_path(pdf), json).map_err(|e| e.to_string())?;
+    Ok(sections)
+}
+
+/// Loads what `extract_pdf` stored for a mirrored PDF.
+pub fn load_sections(pdf: &Path) -> Option<TimetableText> {
+    serde_json::from_str(&fs::read_to_string(sections_path(pdf)).ok()?).ok()
+}
+
+fn collect_pdfs(dir: &Path, pdfs: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_pdfs(&path, pdfs)?;
+        } else if path.extension().is_some_and(|ext| ext == "pdf") {
+            pdfs.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// All mirrored PDFs under `root`, sorted.
+pub fn mirrored_pdfs(root: &str) -> std::io::Result<Vec<PathBuf>> {
+    let mut pdfs = Vec::new();
+    collect_pdfs(Path::new(root), &mut pdfs)?;
+    pdfs.sort();
+    Ok(pdfs)
+}
+
+fn is_up_to_date(pdf: &Path) -> bool {
+    let modified = |path: &Path| fs::metadata(path).and_then(|m| m.modified()).ok();
+    match (modified(pdf), modified(&sections_path(pdf))) {
+        (Some(pdf_time), Some(sections_time)) => sections_time >= pdf_time,
+        _ => false,
+    }
+}
+
+/// Extracts every mirrored PDF whose text is missing or older than the PDF.
+pub fn extract_mirror(root: &str) -> std::io::Result<ExtractSummary> {
+    let mut summary = ExtractSummary::default();
+
+    for pdf in mirrored_pdfs(root)? {
+        if is_up_to_date(&pdf) {
+            summary.up_to_date += 1;
+            continue;
+        }
+        match extract_pdf(&pdf) {
+            Ok(_) => summary.extracted += 1,
+            Err(e) => summary.failed.push((pdf.display().to_string(), e)),
+        }
+    }
+
+    Ok(summary)
+}
@@ -0,0 +1,366 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde::{Serialize, Deserialize};
+
+use crate::_16_mirror::with_suffix;
+use crate::_18_pdf_text::{TimetableText, is_footnote, load_sections, mirrored_pdfs, stop_name};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum DayType {
+    Workdays,
+    Saturdays,
+    Sundays,
+    Holidays,
+}
+
+/// `X jede v pracovních dnech`, `† jede v neděli a ve státem uznané svátky`, ...
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Footnote {
+    /// pictogram or number the trips refer to, when the note starts with one
+    pub symbol: Option<String>,
+    pub text: String,
+    pub day_types: Vec<DayType>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Trip {
+    pub number: Option<String>,
+    pub day_types: Vec<DayType>,
+    /// footnote symbols printed above the trip
+    pub notes: Vec<String>,
+    /// `HH:MM` per stop of the direction, `None` where the trip does not stop
+    pub departures: Vec<Option<String>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Direction {
+    /// `Směr` header when printed, otherwise the last stop
+    pub name: String,
+    pub stops: Vec<String>,
+    pub trips: Vec<Trip>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Timetable {
+    pub file: String,
+    pub line: Option<String>,
+    pub directions: Vec<Direction>,
+    pub footnotes: Vec<Footnote>,
+}
+
+/// What could not be parsed in one file; written to `CanopyResults/timetable_report.json`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ParseReport {
+    pub file: String,
+    pub directions: usize,
+    pub trips: usize,
+    pub stops_without_data: Vec<String>,
+    pub layout_problems: Vec<String>,
+}
+
+impl ParseReport {
+    pub fn is_clean(&self) -> bool {
+        self.stops_without_data.is_empty() && self.layout_problems.is_empty()
+    }
+}
+
+/// ===================== Footnotes =====================
+const DAY_TYPE_MARKERS: &[(&str, &[DayType])] = &[
+    ("pracovních dnech", &[DayType::Workdays]),
+    ("sobot", &[DayType::Saturdays]),
+    ("neděl", &[DayType::Sundays]),
+    ("svátk", &[DayType::Holidays]),
+    ("denně", &[DayType::Workdays, DayType::Saturdays, DayType::Sundays, DayType::Holidays]),
+];
+
+fn day_types(text: &str) -> Vec<DayType> {
+    let lower = text.to_lowercase();
+    let mut types = Vec::new();
+    for (marker, marked) in DAY_TYPE_MARKERS {
+        if lower.contains(marker) {
+            types.extend(marked.iter().filter(|t| !types.contains(*t)).copied().collect::<Vec<_>>());
+        }
+    }
+    types
+}
+
+fn parse_footnote(row: &str) -> Footnote {
+    let mut words = row.split_whitespace();
+    let first = words.next().unwrap_or_default();
+    let is_symbol = first.chars().count() <= 2 && !first.chars().all(char::is_lowercase) && words.next().is_some();
+
+    Footnote {
+        symbol: is_symbol.then(|| first.to_string()),
+        text: row.to_string(),
+        day_types: day_types(row),
+    }
+}
+
+/// ===================== Table rows =====================
+/// `5:07` / `05:07` as `05:07`.
+fn parse_time(token: &str) -> Option<String> {
+    let (hours, minutes) = token.split_once(':')?;
+    let h: u32 = hours.parse().ok()?;
+    let m: u32 = minutes.parse().ok()?;
+    (h < 48 && m < 60 && minutes.len() == 2).then(|| format!("{:02}:{:02}", h, m))
+}
+
+/// Departure cells after the stop name; `|` and `<` mark trips passing or not reaching the stop.
+/// Kilometres, zones and platforms before the first time are skipped.
+fn departure_cells(rest: &str) -> Vec<Option<String>> {
+    let mut cells = Vec::new();
+    for token in rest.split_whitespace() {
+        match (parse_time(token), token) {
+            (Some(time), _) => cells.push(Some(time)),
+            (None, "|" | "<") if !cells.is_empty() => cells.push(None),
+            _ => {}
+        }
+    }
+    cells
+}
+
+/// `Směr Ostrava, Dubina` → `Ostrava, Dubina`
+fn direction_header(row: &str) -> Option<&str> {
+    let name = row.strip_prefix("Směr").or_else(|| row.strip_prefix("směr"))?;
+    Some(name.trim_start_matches([':', ' ']).trim())
+}
+
+/// `Spoj 1 3 5 7` / `Číslo spoje 1 3 5 7`
+fn trip_numbers(row: &str) -> Option<Vec<String>> {
+    let rest = row.strip_prefix("Číslo spoje").or_else(|| row.strip_prefix("Spoj"))?;
+    let numbers: Vec<String> = rest.split_whitespace().map(str::to_string).collect();
+    match !numbers.is_empty() && numbers.iter().all(|n| n.chars().all(|c| c.is_ascii_digit())) {
+        true => Some(numbers),
+        false => None,
+    }
+}
+
+/// ===================== Parser =====================
+#[derive(Default)]
+struct DirectionBuilder {
+    name: Option<String>,
+    trip_numbers: Vec<String>,
+    symbol_rows: Vec<Vec<String>>,
+    rows: Vec<(String, Vec<Option<String>>)>,
+}
+
+impl DirectionBuilder {
+    fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    fn finish(self, footnotes: &[Footnote], report: &mut ParseReport) -> Option<Direction> {
+        let name = self.name.or_else(|| self.rows.last().map(|(stop, _)| stop.clone()))?;
+        let trip_count = self.rows.iter().map(|(_, cells)| cells.len()).max().unwrap_or(0);
+        if trip_count == 0 {
+            report.layout_problems.push(format!("direction {}: no departure times", name));
+            return None;
+        }
+
+        for (stop, cells) in &self.rows {
+            if cells.iter().all(Option::is_none) {
+                report.stops_without_data.push(stop.clone());
+            } else if cells.len() != trip_count {
+                report.layout_problems.push(format!(
+                    "direction {}: {} has {} of {} departures",
+                    name, stop, cells.len(), trip_count
+                ));
+            }
+        }
+        if !self.trip_numbers.is_empty() && self.trip_numbers.len() != trip_count {
+            report.layout_problems.push(format!(
+                "direction {}: {} trip numbers for {} trips",
+                name, self.trip_numbers.len(), trip_count
+            ));
+        }
+
+        let trips = (0..trip_count)
+            .map(|i| {
+                // A symbol row either has one symbol per trip or applies to the whole table
+                let notes: Vec<String> = self
+                    .symbol_rows
+                    .iter()
+                    .filter_map(|row| match row.len() == trip_count {
+                        true => row.get(i).cloned(),
+                        false => (row.len() == 1).then(|| row[0].clone()),
+                    })
+                    .collect();
+                let mut trip_days: Vec<DayType> = Vec::new();
+                for note in footnotes.iter().filter(|f| f.symbol.as_ref().is_some_and(|s| notes.contains(s))) {
+                    trip_days.extend(note.day_types.iter().filter(|t| !trip_days.contains(*t)).copied().collect::<Vec<_>>());
+                }
+
+                Trip {
+                    number: match self.trip_numbers.len() == trip_count {
+                        true => Some(self.trip_numbers[i].clone()),
+                        false => None,
+                    },
+                    day_types: trip_days,
+                    notes,
+                    departures: self.rows.iter().map(|(_, cells)| cells.get(i).cloned().flatten()).collect(),
+                }
+            })
+            .collect();
+
+        Some(Direction {
+            name,
+            stops: self.rows.into_iter().map(|(stop, _)| stop).collect(),
+            trips,
+        })
+    }
+}
+
+/// Turns extracted text into directions, trips and footnotes. A new direction starts at a
+/// `Směr` header, a trip-number row after stops, or when a stop repeats (the return table).
+pub fn parse_timetable(sections: &TimetableText) -> (Timetable, ParseReport) {
+    let mut report = ParseReport {
+        file: sections.file.clone(),
+        ..Default::default()
+    };
+    let rows: Vec<&str> = sections.text.lines().map(str::trim).filter(|l| !l.is_empty()).collect();
+
+    let footnotes: Vec<Footnote> = rows
+        .iter()
+        .filter(|row| is_footnote(row) && trip_numbers(row).is_none() && direction_header(row).is_none())
+        .map(|row| parse_footnote(row))
+        .collect();
+    let symbols: Vec<&String> = footnotes.iter().filter_map(|f| f.symbol.as_ref()).collect();
+
+    let mut directions = Vec::new();
+    let mut current = DirectionBuilder::default();
+
+    for row in rows {
+        if let Some(numbers) = trip_numbers(row) {
+            if !current.is_empty() {
+                directions.extend(std::mem::take(&mut current).finish(&footnotes, &mut report));
+            }
+            current.trip_numbers = numbers;
+        } else if let Some(name) = direction_header(row) {
+            if !current.is_empty() {
+                directions.extend(std::mem::take(&mut current).finish(&footnotes, &mut report));
+            }
+            current.name = Some(name.to_string()).filter(|n| !n.is_empty());
+        } else if is_footnote(row) || sections.route.as_deref() == Some(row) || sections.line_header.as_deref() == Some(row) {
+            continue;
+        } else if row.split_whitespace().all(|token| symbols.iter().any(|s| *s == token)) {
+            current.symbol_rows.push(row.split_whitespace().map(str::to_string).collect());
+        } else if let Some(stop) = stop_name(row) {
+            if current.rows.iter().any(|(seen, _)| *seen == stop) {
+                directions.extend(std::mem::take(&mut current).finish(&footnotes, &mut report));
+            }
+            let rest = row.find(|c: char| c.is_ascii_digit() || c == '|').map_or("", |i| &row[i..]);
+            current.rows.push((stop, departure_cells(rest)));
+        }
+    }
+    if !current.is_empty() {
+        directions.extend(current.finish(&footnotes, &mut report));
+    }
+
+    if directions.is_empty() && report.layout_problems.is_empty() {
+        report.layout_problems.push("no stop rows with departure times".to_string());
+    }
+    report.directions = directions.len();
+    report.trips = directions.iter().map(|d: &Direction| d.trips.len()).sum();
+
+    let timetable = Timetable {
+        file: sections.file.clone(),
+        line: sections.line.clone(),
+        directions,
+        footnotes,
+    };
+    (timetable, report)
+}
+
+/// ===================== Mirror =====================
+fn timetable_path(pdf: &Path) -> PathBuf {
+    with_suffix(pdf, ".timetable.json")
+}
+
+/// Loads what `parse_mirror` stored for a mirrored PDF.
+pub fn load_timetable(pdf: &Path) -> Option<Timetable> {
+    serde_json::from_str(&fs::read_to_string(timetable_path(pdf)).ok()?).ok()
+}
+
+/// Parses every mirrored PDF with extracted text into `.pdf.timetable.json` next to it.
+pub fn parse_mirror(root: &str) -> Result<Vec<ParseReport>, Box<dyn std::error::Error>> {
+    let mut reports = Vec::new();
+
+    for pdf in mirrored_pdfs(root)? {
+        let Some(sections) = load_sections(&pdf) else {
+            reports.push(ParseReport {
+                file: pdf.display().to_string(),
+                layout_problems: vec!["text not extracted (run `export --text`)".to_string()],
+                ..Default::default()
+            });
+            continue;
+        };
+        let (timetable, report) = parse_timetable(&sections);
+        fs::write(timetable_path(&pdf), serde_json::to_string_pretty(&timetable)?)?;
+        reports.push(report);
+    }
+
+    Ok(reports)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sections(text: &str) -> TimetableText {
+        TimetableText {
+            file: "37_2025_12_14_2026_12_12_0123456789.pdf".to_string(),
+            line: Some("37".to_string()),
+            text: text.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn stop_names_containing_footnote_markers_stay_stops() {
+        let text = "Směr Sobotín\nOstrava 5:00 6:00\nSobotín 5:07 6:07\nX jede v pracovních dnech";
+        let (timetable, report) = parse_timetable(&sections(text));
+
+        assert_eq!(timetable.directions.len(), 1);
+        assert_eq!(timetable.directions[0].name, "Sobotín");
+        assert_eq!(timetable.directions[0].stops, ["Ostrava", "Sobotín"]);
+        assert_eq!(timetable.directions[0].trips[1].departures, [Some("06:00".to_string()), Some("06:07".to_string())]);
+        assert_eq!(timetable.footnotes.len(), 1);
+        assert!(report.stops_without_data.is_empty());
+    }
+
+    #[test]
+    fn trip_numbers_and_symbols_give_trips_their_days() {
+        let text = "Spoj 1 3\nX †\nOstrava 5:00 6:00\nSobotín 5:07 |\nX jede v pracovních dnech\n† jede v neděli";
+        let (timetable, report) = parse_timetable(&sections(text));
+
+        let direction = &timetable.directions[0];
+        assert_eq!(direction.name, "Sobotín");
+        assert_eq!(direction.trips[0].number.as_deref(), Some("1"));
+        assert_eq!(direction.trips[0].day_types, [DayType::Workdays]);
+        assert_eq!(direction.trips[1].notes, ["†"]);
+        assert_eq!(direction.trips[1].day_types, [DayType::Sundays]);
+        assert_eq!(direction.trips[1].departures, [Some("06:00".to_string()), None]);
+        assert!(report.is_clean(), "{:?}", report);
+    }
+
+    #[test]
+    fn a_repeated_stop_starts_the_return_direction() {
+        let text = "Ostrava 5:00\nSobotín 5:07\nSobotín 6:00\nOstrava 6:07";
+        let (timetable, report) = parse_timetable(&sections(text));
+
+        let names: Vec<&str> = timetable.directions.iter().map(|d| d.name.as_str()).collect();
+        assert_eq!(names, ["Sobotín", "Ostrava"]);
+        assert_eq!(timetable.directions[1].stops, ["Sobotín", "Ostrava"]);
+        assert_eq!(report.trips, 2);
+    }
+
+    #[test]
+    fn missing_departures_are_reported() {
+        let (_, report) = parse_timetable(&sections("Ostrava 5:00 6:00\nSobotín 5:07"));
+        assert_eq!(report.layout_problems, ["direction Sobotín: Sobotín has 1 of 2 departures"]);
+
+        let (timetable, report) = parse_timetable(&sections("Ostrava\nHavířov"));
+        assert!(timetable.directions.is_empty());
+        assert_eq!(report.layout_problems, ["direction Havířov: no departure times"]);
+    }
+}
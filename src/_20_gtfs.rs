@@ -0,0 +1,502 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use chrono::NaiveDate;
+
+use crate::_02_serialization::ScrapeResults;
+use crate::_05_links::{Area, AreaKind, KODIS_BASE_URL, TimetableFile, parse_timetable_url};
+use crate::_16_mirror::mirror_path;
+use crate::_19_timetable::{DayType, Timetable, load_timetable};
+
+const AGENCY_ID: &str = "KODIS";
+const TIMEZONE: &str = "Europe/Prague";
+
+#[derive(Debug, Clone)]
+pub struct GtfsAgency {
+    pub agency_id: String,
+    pub name: String,
+    pub url: String,
+    pub timezone: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct GtfsRoute {
+    pub route_id: String,
+    pub agency_id: String,
+    pub short_name: String,
+    pub long_name: String,
+    pub route_type: u16,
+}
+
+/// The PDFs carry no coordinates; they come from `stop_coordinates.csv` (see `apply_coordinates`).
+/// A stop without them makes the feed invalid for GTFS consumers, `validate_feed` reports it.
+#[derive(Debug, Clone)]
+pub struct GtfsStop {
+    pub stop_id: String,
+    pub name: String,
+    /// (lat, lon)
+    pub coordinates: Option<(f64, f64)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct GtfsTrip {
+    pub route_id: String,
+    pub service_id: String,
+    pub trip_id: String,
+    pub headsign: String,
+    pub direction_id: u8,
+}
+
+#[derive(Debug, Clone)]
+pub struct GtfsStopTime {
+    pub trip_id: String,
+    /// `HH:MM:SS`; the timetables only print departures, so arrival = departure
+    pub time: String,
+    pub stop_id: String,
+    pub stop_sequence: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct GtfsService {
+    pub service_id: String,
+    /// Monday .. Sunday
+    pub days: [bool; 7],
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+}
+
+#[derive(Debug, Default)]
+pub struct GtfsFeed {
+    pub agency: Vec<GtfsAgency>,
+    pub routes: Vec<GtfsRoute>,
+    pub stops: Vec<GtfsStop>,
+    pub trips: Vec<GtfsTrip>,
+    pub stop_times: Vec<GtfsStopTime>,
+    pub calendar: Vec<GtfsService>,
+}
+
+#[derive(Debug, Default)]
+pub struct ExportSummary {
+    pub timetables: usize,
+    /// (url, reason)
+    pub skipped: Vec<(String, String)>,
+}
+
+/// ===================== Building the feed =====================
+/// Extended GTFS route types: local bus for city MHD, regional bus, suburban (S) and regional (R)
+/// rail, water transport. Without an area the line name decides between train and bus.
+pub fn route_type(area: Option<&Area>, line: &str) -> u16 {
+    let is_train_line = |prefix: char| {
+        line.starts_with(prefix) && line[1..].chars().next().is_some_and(|c| c.is_ascii_digit())
+    };
+    match area.map(|a| a.kind) {
+        Some(AreaKind::City) => 704,
+        Some(AreaKind::Boat) => 1000,
+        Some(AreaKind::Region) => 701,
+        _ if is_train_line('S') => 109,
+        _ if is_train_line('R') => 106,
+        Some(AreaKind::Train) => 100,
+        None => 700,
+    }
+}
+
+/// Stable stop ID from the stop name: `Ostrava,Hlavní nádraží` → `ostrava_hlavní_nádraží`.
+fn stop_id(name: &str) -> String {
+    let id: String = name
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
+        .collect();
+    id.split('_').filter(|part| !part.is_empty()).collect::<Vec<_>>().join("_")
+}
+
+/// Workdays, Saturdays and Sundays map to weekdays; trips without a day type run daily.
+/// Holidays are not modelled (no `calendar_dates.txt`).
+fn service_days(day_types: &[DayType]) -> [bool; 7] {
+    let workdays = day_types.contains(&DayType::Workdays);
+    let days = [
+        workdays,
+        workdays,
+        workdays,
+        workdays,
+        workdays,
+        day_types.contains(&DayType::Saturdays),
+        day_types.contains(&DayType::Sundays),
+    ];
+    match days.iter().any(|d| *d) {
+        true => days,
+        false => [true; 7],
+    }
+}
+
+fn days_code(days: &[bool; 7]) -> String {
+    days.iter().map(|d| if *d { '1' } else { '0' }).collect()
+}
+
+fn add_timetable(
+    feed: &mut GtfsFeed,
+    routes: &mut BTreeMap<String, GtfsRoute>,
+    stops: &mut BTreeMap<String, GtfsStop>,
+    services: &mut BTreeMap<String, GtfsService>,
+    timetable: &Timetable,
+    file: &TimetableFile,
+    stem: &str,
+) {
+    let line = &file.line;
+    if let Some(route) = routes.get_mut(line)
+        && route.long_name.is_empty()
+        && let Some(first) = timetable.directions.first()
+        && let (Some(from), Some(to)) = (first.stops.first(), first.stops.last())
+    {
+        route.long_name = format!("{} - {}", from, to);
+    }
+
+    for (d, direction) in timetable.directions.iter().enumerate() {
+        for stop in &direction.stops {
+            stops.entry(stop_id(stop)).or_insert_with(|| GtfsStop {
+                stop_id: stop_id(stop),
+                name: stop.clone(),
+                coordinates: None,
+            });
+        }
+
+        for (t, trip) in direction.trips.iter().enumerate() {
+            let stop_times: Vec<(&String, &String)> = direction
+                .stops
+                .iter()
+                .zip(&trip.departures)
+                .filter_map(|(stop, time)| time.as_ref().map(|time| (stop, time)))
+                .collect();
+            if stop_times.len() < 2 {
+                continue;
+            }
+
+            let days = service_days(&trip.day_types);
+            let service_id = format!("{}_{}", stem, days_code(&days));
+            services.entry(service_id.clone()).or_insert_with(|| GtfsService {
+                service_id: service_id.clone(),
+                days,
+                start_date: file.valid_from,
+                end_date: file.valid_to,
+            });
+
+            let trip_id = format!("{}_{}_{}", stem, d, t);
+            feed.trips.push(GtfsTrip {
+                route_id: line.clone(),
+                service_id,
+                trip_id: trip_id.clone(),
+                headsign: direction.name.clone(),
+                direction_id: (d % 2) as u8,
+            });
+            feed.stop_times.extend(stop_times.into_iter().enumerate().map(|(i, (stop, time))| GtfsStopTime {
+                trip_id: trip_id.clone(),
+                time: format!("{}:00", time),
+                stop_id: stop_id(stop),
+                stop_sequence: i as u32 + 1,
+            }));
+        }
+    }
+}
+
+/// Builds a feed from the parsed timetables of the mirrored PDFs in `urls`.
+/// Temporary (`_v_`) timetables are left out: they replace regular trips on some days,
+/// which plain `calendar.txt` cannot express.
+pub fn build_feed(results: &ScrapeResults, urls: &[String], mirror_root: &str) -> (GtfsFeed, ExportSummary) {
+    let mut feed = GtfsFeed {
+        agency: vec![GtfsAgency {
+            agency_id: AGENCY_ID.to_string(),
+            name: "KODIS".to_string(),
+            url: KODIS_BASE_URL.to_string(),
+            timezone: TIMEZONE.to_string(),
+        }],
+        ..Default::default()
+    };
+    let mut summary = ExportSummary::default();
+    let mut routes: BTreeMap<String, GtfsRoute> = BTreeMap::new();
+    let mut stops: BTreeMap<String, GtfsStop> = BTreeMap::new();
+    let mut services: BTreeMap<String, GtfsService> = BTreeMap::new();
+
+    for url in urls {
+        let Some(file) = parse_timetable_url(url) else {
+            summary.skipped.push((url.clone(), "not a timetable file name".to_string()));
+            continue;
+        };
+        if file.temporary {
+            summary.skipped.push((url.clone(), "temporary timetable".to_string()));
+            continue;
+        }
+        let Some(timetable) = load_timetable(&mirror_path(mirror_root, url)) else {
+            summary.skipped.push((url.clone(), "not parsed (run `export --mirror --text --timetables`)".to_string()));
+            continue;
+        };
+
+        routes.entry(file.line.clone()).or_insert_with(|| GtfsRoute {
+            route_id: file.line.clone(),
+            agency_id: AGENCY_ID.to_string(),
+            short_name: file.line.clone(),
+            long_name: String::new(),
+            route_type: route_type(results.area_of(url), &file.line),
+        });
+        let stem = url.rsplit('/').next().unwrap_or(url).trim_end_matches(".pdf").to_string();
+        add_timetable(&mut feed, &mut routes, &mut stops, &mut services, &timetable, &file, &stem);
+        summary.timetables += 1;
+    }
+
+    feed.routes = routes.into_values().collect();
+    feed.stops = stops.into_values().collect();
+    feed.calendar = services.into_values().collect();
+    (feed, summary)
+}
+
+/// ===================== Stop coordinates =====================
+/// `stop_name,stop_lat,stop_lon` rows keyed by stop ID; no file means no coordinates.
+pub fn load_stop_coordinates(path: &str) -> Result<HashMap<String, (f64, f64)>, Box<dyn std::error::Error>> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(HashMap::new()),
+        Err(e) => return Err(format!("cannot read {}: {}", path, e).into()),
+    };
+
+    let mut coordinates = HashMap::new();
+    for (i, row) in content.lines().enumerate().skip(1).filter(|(_, row)| !row.trim().is_empty()) {
+        // the name is the only field that may contain commas
+        let mut fields = row.rsplitn(3, ',');
+        let (Some(lon), Some(lat), Some(name)) = (fields.next(), fields.next(), fields.next()) else {
+            return Err(format!("{} line {}: expected stop_name,stop_lat,stop_lon", path, i + 1).into());
+        };
+        let number = |v: &str| v.trim().parse::<f64>().map_err(|_| format!("{} line {}: bad coordinate {}", path, i + 1, v));
+        coordinates.insert(stop_id(name.trim().trim_matches('"')), (number(lat)?, number(lon)?));
+    }
+    Ok(coordinates)
+}
+
+/// Fills in the stops found in `coordinates`; returns how many are still without them.
+pub fn apply_coordinates(feed: &mut GtfsFeed, coordinates: &HashMap<String, (f64, f64)>) -> usize {
+    for stop in &mut feed.stops {
+        stop.coordinates = coordinates.get(&stop.stop_id).copied();
+    }
+    feed.stops.iter().filter(|stop| stop.coordinates.is_none()).count()
+}
+
+/// ===================== Validation =====================
+fn duplicates<'a>(table: &str, ids: impl Iterator<Item = &'a String>, problems: &mut Vec<String>) -> HashSet<&'a String> {
+    let mut seen = HashSet::new();
+    for id in ids {
+        if !seen.insert(id) {
+            problems.push(format!("{}: duplicate id {}", table, id));
+        }
+    }
+    seen
+}
+
+/// Referential integrity of the feed: every reference points to an existing row, IDs are unique,
+/// every trip has at least two stop times in increasing sequence and time, and nothing is orphaned.
+/// Stops without coordinates are reported too, GTFS requires them for every stop.
+pub fn validate_feed(feed: &GtfsFeed) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    let agencies = duplicates("agency.txt", feed.agency.iter().map(|a| &a.agency_id), &mut problems);
+    let routes = duplicates("routes.txt", feed.routes.iter().map(|r| &r.route_id), &mut problems);
+    let stops = duplicates("stops.txt", feed.stops.iter().map(|s| &s.stop_id), &mut problems);
+    let trips = duplicates("trips.txt", feed.trips.iter().map(|t| &t.trip_id), &mut problems);
+    let services = duplicates("calendar.txt", feed.calendar.iter().map(|s| &s.service_id), &mut problems);
+
+    for route in &feed.routes {
+        if !agencies.contains(&route.agency_id) {
+            problems.push(format!("routes.txt: {} references unknown agency {}", route.route_id, route.agency_id));
+        }
+    }
+    for trip in &feed.trips {
+        if !routes.contains(&trip.route_id) {
+            problems.push(format!("trips.txt: {} references unknown route {}", trip.trip_id, trip.route_id));
+        }
+        if !services.contains(&trip.service_id) {
+            problems.push(format!("trips.txt: {} references unknown service {}", trip.trip_id, trip.service_id));
+        }
+    }
+    for service in &feed.calendar {
+        if service.start_date > service.end_date {
+            problems.push(format!("calendar.txt: {} ends before it starts", service.service_id));
+        }
+    }
+
+    let mut times_by_trip: HashMap<&String, Vec<&GtfsStopTime>> = HashMap::new();
+    for stop_time in &feed.stop_times {
+        if !trips.contains(&stop_time.trip_id) {
+            problems.push(format!("stop_times.txt: unknown trip {}", stop_time.trip_id));
+        }
+        if !stops.contains(&stop_time.stop_id) {
+            problems.push(format!("stop_times.txt: {} references unknown stop {}", stop_time.trip_id, stop_time.stop_id));
+        }
+        times_by_trip.entry(&stop_time.trip_id).or_default().push(stop_time);
+    }
+    for trip in &feed.trips {
+        let times = times_by_trip.get(&trip.trip_id).map(Vec::as_slice).unwrap_or_default();
+        if times.len() < 2 {
+            problems.push(format!("trips.txt: {} has {} stop time(s)", trip.trip_id, times.len()));
+        }
+        // `HH:MM:SS` with two-digit hours compares correctly as text
+        if times.windows(2).any(|w| w[1].stop_sequence <= w[0].stop_sequence || w[1].time < w[0].time) {
+            problems.push(format!("stop_times.txt: {} is not in increasing sequence and time", trip.trip_id));
+        }
+    }
+
+    let used_routes: HashSet<&String> = feed.trips.iter().map(|t| &t.route_id).collect();
+    let used_services: HashSet<&String> = feed.trips.iter().map(|t| &t.service_id).collect();
+    let used_stops: HashSet<&String> = feed.stop_times.iter().map(|s| &s.stop_id).collect();
+    problems.extend(routes.difference(&used_routes).map(|id| format!("routes.txt: {} has no trips", id)));
+    problems.extend(services.difference(&used_services).map(|id| format!("calendar.txt: {} is not used", id)));
+    problems.extend(stops.difference(&used_stops).map(|id| format!("stops.txt: {} has no stop times", id)));
+    problems.extend(
+        feed.stops
+            .iter()
+            .filter(|stop| stop.coordinates.is_none())
+            .map(|stop| format!("stops.txt: {} has no stop_lat/stop_lon", stop.stop_id)),
+    );
+
+    problems
+}
+
+/// ===================== Writing =====================
+fn csv_field(value: &str) -> String {
+    match value.contains([',', '"', '\n']) {
+        true => format!("\"{}\"", value.replace('"', "\"\"")),
+        false => value.to_string(),
+    }
+}
+
+/// CSV text with a header row; also used for the change-notice export.
+pub fn csv_table(header: &[&str], rows: impl Iterator<Item = Vec<String>>) -> String {
+    let mut out = header.join(",") + "\n";
+    for row in rows {
+        out.push_str(&row.iter().map(|v| csv_field(v)).collect::<Vec<_>>().join(","));
+        out.push('\n');
+    }
+    out
+}
+
+fn feed_files(feed: &GtfsFeed) -> Vec<(&'static str, String)> {
+    let date = |d: &NaiveDate| d.format("%Y%m%d").to_string();
+    let flag = |b: &bool| if *b { "1" } else { "0" }.to_string();
+
+    vec![
+        ("agency.txt", csv_table(
+            &["agency_id", "agency_name", "agency_url", "agency_timezone", "agency_lang"],
+            feed.agency.iter().map(|a| vec![a.agency_id.clone(), a.name.clone(), a.url.clone(), a.timezone.clone(), "cs".to_string()]),
+        )),
+        ("routes.txt", csv_table(
+            &["route_id", "agency_id", "route_short_name", "route_long_name", "route_type"],
+            feed.routes.iter().map(|r| vec![r.route_id.clone(), r.agency_id.clone(), r.short_name.clone(), r.long_name.clone(), r.route_type.to_string()]),
+        )),
+        ("stops.txt", csv_table(
+            &["stop_id", "stop_name", "stop_lat", "stop_lon"],
+            feed.stops.iter().map(|s| {
+                let (lat, lon) = s.coordinates.map_or((String::new(), String::new()), |(lat, lon)| (lat.to_string(), lon.to_string()));
+                vec![s.stop_id.clone(), s.name.clone(), lat, lon]
+            }),
+        )),
+        ("trips.txt", csv_table(
+            &["route_id", "service_id", "trip_id", "trip_headsign", "direction_id"],
+            feed.trips.iter().map(|t| vec![t.route_id.clone(), t.service_id.clone(), t.trip_id.clone(), t.headsign.clone(), t.direction_id.to_string()]),
+        )),
+        ("stop_times.txt", csv_table(
+            &["trip_id", "arrival_time", "departure_time", "stop_id", "stop_sequence"],
+            feed.stop_times.iter().map(|s| vec![s.trip_id.clone(), s.time.clone(), s.time.clone(), s.stop_id.clone(), s.stop_sequence.to_string()]),
+        )),
+        ("calendar.txt", csv_table(
+            &["service_id", "monday", "tuesday", "wednesday", "thursday", "friday", "saturday", "sunday", "start_date", "end_date"],
+            feed.calendar.iter().map(|s| {
+                let mut row = vec![s.service_id.clone()];
+                row.extend(s.days.iter().map(flag));
+                row.extend([date(&s.start_date), date(&s.end_date)]);
+                row
+            }),
+        )),
+    ]
+}
+
+/// Writes the feed as text files into `dir` and as `{dir}.zip`.
+pub fn write_feed(feed: &GtfsFeed, dir: &str) -> Result<(), Box<dyn std::error::Error>> {
+    fs::create_dir_all(dir)?;
+    let mut zip = zip::ZipWriter::new(fs::File::create(format!("{}.zip", dir.trim_end_matches('/')))?);
+    let options = zip::write::SimpleFileOptions::default();
+
+    for (name, content) in feed_files(feed) {
+        fs::write(Path::new(dir).join(name), &content)?;
+        zip.start_file(name, options)?;
+        zip.write_all(content.as_bytes())?;
+    }
+    zip.finish()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// One route with one trip over two stops.
+    fn feed() -> GtfsFeed {
+        let date = |m| NaiveDate::from_ymd_opt(2026, m, 1).unwrap();
+        let stop = |id: &str| GtfsStop { stop_id: id.to_string(), name: id.to_string(), coordinates: Some((49.8, 18.2)) };
+        let stop_time = |stop_id: &str, time: &str, stop_sequence| GtfsStopTime {
+            trip_id: "t1".to_string(),
+            time: time.to_string(),
+            stop_id: stop_id.to_string(),
+            stop_sequence,
+        };
+
+        GtfsFeed {
+            agency: vec![GtfsAgency {
+                agency_id: AGENCY_ID.to_string(),
+                name: "KODIS".to_string(),
+                url: KODIS_BASE_URL.to_string(),
+                timezone: TIMEZONE.to_string(),
+            }],
+            routes: vec![GtfsRoute {
+                route_id: "37".to_string(),
+                agency_id: AGENCY_ID.to_string(),
+                short_name: "37".to_string(),
+                long_name: String::new(),
+                route_type: 3,
+            }],
+            stops: vec![stop("ostrava"), stop("sobotin")],
+            trips: vec![GtfsTrip {
+                route_id: "37".to_string(),
+                service_id: "s1".to_string(),
+                trip_id: "t1".to_string(),
+                headsign: "Sobotín".to_string(),
+                direction_id: 0,
+            }],
+            stop_times: vec![stop_time("ostrava", "05:00:00", 1), stop_time("sobotin", "05:07:00", 2)],
+            calendar: vec![GtfsService { service_id: "s1".to_string(), days: [true; 7], start_date: date(1), end_date: date(12) }],
+        }
+    }
+
+    #[test]
+    fn a_consistent_feed_has_no_problems() {
+        assert!(validate_feed(&feed()).is_empty(), "{:?}", validate_feed(&feed()));
+    }
+
+    #[test]
+    fn broken_references_and_order_are_reported() {
+        let mut feed = feed();
+        feed.stop_times[1].stop_id = "nowhere".to_string();
+        feed.stop_times[1].time = "04:59:00".to_string();
+        feed.trips[0].service_id = "s2".to_string();
+
+        let problems = validate_feed(&feed);
+        assert!(problems.contains(&"stop_times.txt: t1 references unknown stop nowhere".to_string()));
+        assert!(problems.contains(&"stop_times.txt: t1 is not in increasing sequence and time".to_string()));
+        assert!(problems.contains(&"trips.txt: t1 references unknown service s2".to_string()));
+        assert!(problems.contains(&"calendar.txt: s1 is not used".to_string()));
+        assert!(problems.contains(&"stops.txt: sobotin has no stop times".to_string()));
+    }
+
+    #[test]
+    fn stops_without_coordinates_are_reported() {
+        let mut feed = feed();
+        feed.stops[0].coordinates = None;
+        assert_eq!(validate_feed(&feed), ["stops.txt: ostrava has no stop_lat/stop_lon"]);
+    }
+}
@@ -0,0 +1,186 @@
+use std::collections::{BTreeMap, HashMap};
+use futures::stream::{self, StreamExt};
+use reqwest::header;
+use serde::{Serialize, Deserialize};
+
+use crate::_02_serialization::{LinkKind, ScrapeResults};
+use crate::_17_link_check::head_client;
+
+/// S3 object headers of a link, from a HEAD request.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ObjectMeta {
+    pub content_length: Option<u64>,
+    pub last_modified: Option<String>,
+    pub etag: Option<String>,
+}
+
+/// Same URL, different object: KODIS re-uploaded the file without renaming it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplacedObject {
+    pub url: String,
+    pub before: ObjectMeta,
+    pub after: ObjectMeta,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct AreaSize {
+    pub files: usize,
+    pub bytes: u64,
+    /// files whose HEAD had no `Content-Length`
+    pub unknown: usize,
+}
+
+async fn head_object(client: &reqwest::Client, url: &str) -> Result<ObjectMeta, String> {
+    let response = client.head(url).send().await.map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        return Err(format!("HTTP {}", response.status()));
+    }
+    let text = |name: header::HeaderName| {
+        response.headers().get(name).and_then(|v| v.to_str().ok()).map(str::to_string)
+    };
+
+    Ok(ObjectMeta {
+        content_length: text(header::CONTENT_LENGTH).and_then(|v| v.parse().ok()),
+        last_modified: text(header::LAST_MODIFIED),
+        etag: text(header::ETAG),
+    })
+}
+
+/// HEADs every PDF link (at most `max_concurrency` at a time) and stores the headers on the link.
+/// Returns the links that could not be enriched, with the reason.
+pub async fn enrich_links(results: &mut ScrapeResults, max_concurrency: usize) -> reqwest::Result<Vec<(String, String)>> {
+    let client = head_client()?;
+    let client = &client;
+    let urls: Vec<String> = results
+        .links
+        .iter()
+        .filter(|link| link.kind == LinkKind::Pdf)
+        .map(|link| link.url.clone())
+        .collect();
+
+    let fetched: HashMap<String, Result<ObjectMeta, String>> = stream::iter(urls)
+        .map(|url| async move {
+            let meta = head_object(client, &url).await;
+            (url, meta)
+        })
+        .buffer_unordered(max_concurrency.max(1))
+        .collect()
+        .await;
+
+    let mut failed = Vec::new();
+    for link in &mut results.links {
+        match fetched.get(&link.url) {
+            Some(Ok(meta)) => link.object = Some(meta.clone()),
+            Some(Err(e)) => failed.push((link.url.clone(), e.clone())),
+            None => {}
+        }
+    }
+    failed.sort();
+    Ok(failed)
+}
+
+fn differs<T: PartialEq>(a: &Option<T>, b: &Option<T>) -> bool {
+    matches!((a, b), (Some(a), Some(b)) if a != b)
+}
+
+/// The ETag decides when both sides have one; otherwise a different size or `Last-Modified` does.
+fn is_replaced(before: &ObjectMeta, after: &ObjectMeta) -> bool {
+    match (&before.etag, &after.etag) {
+        (Some(a), Some(b)) => a != b,
+        _ => differs(&before.content_length, &after.content_length) || differs(&before.last_modified, &after.last_modified),
+    }
+}
+
+/// Links present in both runs whose object changed behind the unchanged URL.
+pub fn replaced_objects(previous: &ScrapeResults, current: &ScrapeResults) -> Vec<ReplacedObject> {
+    let before: HashMap<&String, &ObjectMeta> = previous
+        .links
+        .iter()
+        .filter_map(|link| link.object.as_ref().map(|meta| (&link.url, meta)))
+        .collect();
+
+    current
+        .links
+        .iter()
+        .filter_map(|link| {
+            let after = link.object.as_ref()?;
+            let before = *before.get(&link.url)?;
+            is_replaced(before, after).then(|| ReplacedObject {
+                url: link.url.clone(),
+                before: before.clone(),
+                after: after.clone(),
+            })
+        })
+        .collect()
+}
+
+/// Keeps the last known headers of links that were not (or could not be) enriched in this run,
+/// so the next enrichment still has something to compare against.
+pub fn carry_over(previous: &ScrapeResults, current: &mut ScrapeResults) {
+    let known: HashMap<&String, &ObjectMeta> = previous
+        .links
+        .iter()
+        .filter_map(|link| link.object.as_ref().map(|meta| (&link.url, meta)))
+        .collect();
+
+    for link in current.links.iter_mut().filter(|link| link.object.is_none()) {
+        link.object = known.get(&link.url).map(|meta| (*meta).clone());
+    }
+}
+
+/// Download size of the PDFs per tab; links not listed on a tab (change notices) count as `changes`.
+pub fn size_by_area(results: &ScrapeResults) -> BTreeMap<String, AreaSize> {
+    let mut sizes: BTreeMap<String, AreaSize> = BTreeMap::new();
+
+    for link in results.links.iter().filter(|link| link.kind == LinkKind::Pdf) {
+        let area = link.areas.first().map_or("changes".to_string(), |area| area.to_string());
+        let size = sizes.entry(area).or_default();
+        size.files += 1;
+        match link.object.as_ref().and_then(|meta| meta.content_length) {
+            Some(bytes) => size.bytes += bytes,
+            None => size.unknown += 1,
+        }
+    }
+
+    sizes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn meta(content_length: Option<u64>, last_modified: Option<&str>, etag: Option<&str>) -> ObjectMeta {
+        ObjectMeta {
+            content_length,
+            last_modified: last_modified.map(str::to_string),
+            etag: etag.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn a_different_etag_means_replaced() {
+        let before = meta(Some(100), Some("Wed, 17 Dec 2025 08:00:00 GMT"), Some("\"a\""));
+        assert!(is_replaced(&before, &meta(Some(100), Some("Wed, 17 Dec 2025 08:00:00 GMT"), Some("\"b\""))));
+    }
+
+    #[test]
+    fn the_same_etag_is_still_current_even_if_other_headers_differ() {
+        let before = meta(Some(100), Some("Wed, 17 Dec 2025 08:00:00 GMT"), Some("\"a\""));
+        assert!(!is_replaced(&before, &meta(Some(100), Some("Thu, 18 Dec 2025 08:00:00 GMT"), Some("\"a\""))));
+        assert!(!is_replaced(&before, &before.clone()));
+    }
+
+    #[test]
+    fn without_etags_size_or_date_decide() {
+        let before = meta(Some(100), Some("Wed, 17 Dec 2025 08:00:00 GMT"), None);
+        assert!(is_replaced(&before, &meta(Some(120), Some("Wed, 17 Dec 2025 08:00:00 GMT"), Some("\"b\""))));
+        assert!(is_replaced(&before, &meta(Some(100), Some("Thu, 18 Dec 2025 08:00:00 GMT"), None)));
+        assert!(!is_replaced(&before, &meta(Some(100), Some("Wed, 17 Dec 2025 08:00:00 GMT"), None)));
+    }
+
+    #[test]
+    fn a_missing_header_is_no_evidence_of_a_replacement() {
+        let before = meta(Some(100), None, None);
+        assert!(!is_replaced(&before, &meta(None, Some("Wed, 17 Dec 2025 08:00:00 GMT"), None)));
+    }
+}
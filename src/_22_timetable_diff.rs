@@ -0,0 +1,317 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use serde::{Serialize, Deserialize};
+
+use crate::_05_links::{TimetableFile, parse_timetable_url};
+use crate::_18_pdf_text::{load_sections, mirrored_pdfs};
+use crate::_19_timetable::{DayType, Direction, Trip, load_timetable};
+
+/// Departures further apart than this are a cancellation plus a new trip, not a shift.
+const MAX_SHIFT_MINUTES: i32 = 20;
+
+/// A trip that runs at another time; times are at the first stop of the trip both versions share.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TripShift {
+    pub trip: String,
+    pub stop: String,
+    pub before: String,
+    pub after: String,
+    pub minutes: i32,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct DirectionDiff {
+    pub direction: String,
+    pub added_stops: Vec<String>,
+    pub removed_stops: Vec<String>,
+    /// `05:07 X` (first departure and day types) or the trip number
+    pub added_trips: Vec<String>,
+    pub cancelled_trips: Vec<String>,
+    pub shifted_trips: Vec<TripShift>,
+}
+
+impl DirectionDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added_stops.is_empty()
+            && self.removed_stops.is_empty()
+            && self.added_trips.is_empty()
+            && self.cancelled_trips.is_empty()
+            && self.shifted_trips.is_empty()
+    }
+}
+
+/// What changed between two versions of a line's timetable.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct TimetableDiff {
+    pub line: String,
+    pub before: String,
+    pub after: String,
+    /// `false` when one side was not parsed: only the stop lists of the extracted text are compared
+    pub parsed: bool,
+    pub added_stops: Vec<String>,
+    pub removed_stops: Vec<String>,
+    pub directions: Vec<DirectionDiff>,
+}
+
+fn minutes(time: &str) -> Option<i32> {
+    let (h, m) = time.split_once(':')?;
+    Some(h.parse::<i32>().ok()? * 60 + m.parse::<i32>().ok()?)
+}
+
+fn stop_changes(before: &[String], after: &[String]) -> (Vec<String>, Vec<String>) {
+    let added = after.iter().filter(|s| !before.contains(s)).cloned().collect();
+    let removed = before.iter().filter(|s| !after.contains(s)).cloned().collect();
+    (added, removed)
+}
+
+/// A trip as seen at one stop, for matching between versions.
+struct TripAt<'a> {
+    trip: &'a Trip,
+    stop: String,
+    time: String,
+}
+
+impl TripAt<'_> {
+    fn label(&self) -> String {
+        let days: String = self
+            .trip
+            .day_types
+            .iter()
+            .map(|d| match d {
+                DayType::Workdays => "X",
+                DayType::Saturdays => "6",
+                DayType::Sundays => "7",
+                DayType::Holidays => "+",
+            })
+            .collect();
+        match (&self.trip.number, days.is_empty()) {
+            (Some(number), _) => format!("spoj {} ({})", number, self.time),
+            (None, true) => self.time.clone(),
+            (None, false) => format!("{} {}", self.time, days),
+        }
+    }
+}
+
+/// Every trip at the first of the `shared` stops it serves, so short turns and trips starting
+/// mid-route are compared too; a trip serving none of them is taken at its first stop.
+fn trips_at<'a>(direction: &'a Direction, shared: &[&String]) -> Vec<TripAt<'a>> {
+    let departure = |trip: &Trip, stop: &String| {
+        let i = direction.stops.iter().position(|s| s == stop)?;
+        trip.departures.get(i)?.clone().map(|time| (stop.clone(), time))
+    };
+    direction
+        .trips
+        .iter()
+        .filter_map(|trip| {
+            let (stop, time) = shared
+                .iter()
+                .find_map(|stop| departure(trip, stop))
+                .or_else(|| direction.stops.iter().find_map(|stop| departure(trip, stop)))?;
+            Some(TripAt { trip, stop, time })
+        })
+        .collect()
+}
+
+/// Trips are matched by number when both versions print one, otherwise by exact stop, time and day types;
+/// what is left is paired as a shift when the stop and day types agree and the times are close.
+fn diff_direction(before: &Direction, after: &Direction) -> DirectionDiff {
+    let (added_stops, removed_stops) = stop_changes(&before.stops, &after.stops);
+    let mut diff = DirectionDiff {
+        direction: after.name.clone(),
+        added_stops,
+        removed_stops,
+        ..Default::default()
+    };
+    let shared: Vec<&String> = after.stops.iter().filter(|s| before.stops.contains(s)).collect();
+    if shared.is_empty() {
+        return diff;
+    }
+
+    let mut old = trips_at(before, &shared);
+    let mut new = trips_at(after, &shared);
+    let same_trip = |a: &TripAt, b: &TripAt| match (&a.trip.number, &b.trip.number) {
+        (Some(x), Some(y)) => x == y,
+        _ => a.stop == b.stop && a.time == b.time && a.trip.day_types == b.trip.day_types,
+    };
+
+    let mut shifted = Vec::new();
+    new.retain(|n| match old.iter().position(|o| same_trip(o, n)) {
+        Some(i) => {
+            let o = old.remove(i);
+            if o.time != n.time {
+                shifted.push((o, n.time.clone()));
+            }
+            false
+        }
+        None => true,
+    });
+
+    new.retain(|n| {
+        let closest = old
+            .iter()
+            .enumerate()
+            .filter(|(_, o)| o.stop == n.stop && o.trip.day_types == n.trip.day_types)
+            .filter_map(|(i, o)| Some((i, (minutes(&n.time)? - minutes(&o.time)?).abs())))
+            .filter(|(_, distance)| *distance <= MAX_SHIFT_MINUTES)
+            .min_by_key(|(_, distance)| *distance);
+        match closest {
+            Some((i, _)) => {
+                shifted.push((old.remove(i), n.time.clone()));
+                false
+            }
+            None => true,
+        }
+    });
+
+    diff.shifted_trips = shifted
+        .into_iter()
+        .map(|(o, after)| TripShift {
+            trip: o.label(),
+            stop: o.stop.clone(),
+            minutes: minutes(&after).zip(minutes(&o.time)).map_or(0, |(a, b)| a - b),
+            before: o.time,
+            after,
+        })
+        .collect();
+    diff.cancelled_trips = old.iter().map(TripAt::label).collect();
+    diff.added_trips = new.iter().map(TripAt::label).collect();
+    diff
+}
+
+/// Compares two mirrored PDFs of the same line, from their parsed timetables or, when one of them
+/// was not parsed, from the stop lists of the extracted text.
+pub fn diff_files(before: &Path, after: &Path) -> Option<TimetableDiff> {
+    let name = |path: &Path| path.file_name().and_then(|n| n.to_str()).unwrap_or_default().to_string();
+    let mut diff = TimetableDiff {
+        line: parse_timetable_url(&name(after)).map(|f| f.line).unwrap_or_default(),
+        before: name(before),
+        after: name(after),
+        ..Default::default()
+    };
+
+    match (load_timetable(before), load_timetable(after)) {
+        (Some(old), Some(new)) => {
+            let all_stops = |directions: &[Direction]| {
+                let mut stops: Vec<String> = Vec::new();
+                for stop in directions.iter().flat_map(|d| &d.stops) {
+                    if !stops.contains(stop) {
+                        stops.push(stop.clone());
+                    }
+                }
+                stops
+            };
+            (diff.added_stops, diff.removed_stops) = stop_changes(&all_stops(&old.directions), &all_stops(&new.directions));
+            diff.parsed = true;
+
+            for (i, direction) in new.directions.iter().enumerate() {
+                let previous = old.directions.iter().find(|d| d.name == direction.name).or_else(|| old.directions.get(i));
+                if let Some(previous) = previous {
+                    diff.directions.push(diff_direction(previous, direction));
+                }
+            }
+        }
+        _ => {
+            let (old, new) = (load_sections(before)?, load_sections(after)?);
+            (diff.added_stops, diff.removed_stops) = stop_changes(&old.stops, &new.stops);
+        }
+    }
+
+    Some(diff)
+}
+
+/// Regular (non-temporary) mirrored versions of every line, oldest first.
+pub fn line_versions(root: &str) -> std::io::Result<BTreeMap<String, Vec<(TimetableFile, PathBuf)>>> {
+    let mut lines: BTreeMap<String, Vec<(TimetableFile, PathBuf)>> = BTreeMap::new();
+
+    for pdf in mirrored_pdfs(root)? {
+        let name = pdf.file_name().and_then(|n| n.to_str()).unwrap_or_default().to_string();
+        if let Some(file) = parse_timetable_url(&name)
+            && !file.temporary
+        {
+            lines.entry(file.line.clone()).or_default().push((file, pdf));
+        }
+    }
+    for versions in lines.values_mut() {
+        versions.sort_by_key(|(file, _)| (file.valid_from, file.valid_to));
+    }
+
+    Ok(lines)
+}
+
+/// Diff of the two most recent versions of `line` in the mirror.
+pub fn diff_latest(root: &str, line: &str) -> Result<TimetableDiff, Box<dyn std::error::Error>> {
+    let lines = line_versions(root)?;
+    let versions = lines.get(line).map(Vec::as_slice).unwrap_or_default();
+    let [.., (_, before), (_, after)] = versions else {
+        return Err(format!("line {}: fewer than two mirrored versions", line).into());
+    };
+    diff_files(before, after).ok_or_else(|| format!("line {}: text not extracted (run `export --text`)", line).into())
+}
+
+pub fn print_diff(diff: &TimetableDiff) {
+    println!("Line {}: {} → {}", diff.line, diff.before, diff.after);
+    if !diff.parsed {
+        println!("  (not parsed, comparing stop lists only)");
+    }
+    for stop in &diff.added_stops {
+        println!("  + stop {}", stop);
+    }
+    for stop in &diff.removed_stops {
+        println!("  - stop {}", stop);
+    }
+    for direction in diff.directions.iter().filter(|d| !d.is_empty()) {
+        println!("  Direction {}:", direction.direction);
+        for trip in &direction.added_trips {
+            println!("    + {}", trip);
+        }
+        for trip in &direction.cancelled_trips {
+            println!("    - {}", trip);
+        }
+        for shift in &direction.shifted_trips {
+            println!("    ~ {}: {} → {} at {} ({:+} min)", shift.trip, shift.before, shift.after, shift.stop, shift.minutes);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trip(departures: [Option<&str>; 3]) -> Trip {
+        Trip {
+            number: None,
+            day_types: vec![DayType::Workdays],
+            notes: Vec::new(),
+            departures: departures.iter().map(|d| d.map(str::to_string)).collect(),
+        }
+    }
+
+    fn direction(trips: Vec<Trip>) -> Direction {
+        Direction {
+            name: "Dubina".to_string(),
+            stops: ["Hlavní nádraží", "Náměstí", "Dubina"].map(String::from).to_vec(),
+            trips,
+        }
+    }
+
+    #[test]
+    fn trips_not_serving_the_first_shared_stop_are_compared() {
+        let before = direction(vec![
+            trip([Some("05:00"), Some("05:05"), Some("05:10")]),
+            trip([None, Some("06:05"), Some("06:10")]),
+            trip([None, Some("07:05"), Some("07:10")]),
+        ]);
+        let after = direction(vec![
+            trip([Some("05:00"), Some("05:05"), Some("05:10")]),
+            trip([None, Some("06:08"), Some("06:13")]),
+            trip([None, None, Some("08:10")]),
+        ]);
+
+        let diff = diff_direction(&before, &after);
+        assert_eq!(diff.shifted_trips.len(), 1);
+        assert_eq!(diff.shifted_trips[0].stop, "Náměstí");
+        assert_eq!(diff.shifted_trips[0].minutes, 3);
+        assert_eq!(diff.cancelled_trips, ["07:05 X"]);
+        assert_eq!(diff.added_trips, ["08:10 X"]);
+    }
+}
@@ -0,0 +1,251 @@
+use std::fs;
+use chrono::NaiveDate;
+use serde::{Serialize, Deserialize};
+
+use crate::_02_serialization::{deserialize_from_json, serialize_to_json};
+use crate::_05_links::parse_timetable_url;
+use crate::_20_gtfs::csv_table;
+
+/// One `/changes/{id}` page: the announcement text, not just its PDFs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChangeNotice {
+    pub id: i32,
+    pub url: String,
+    pub title: String,
+    pub published: Option<NaiveDate>,
+    pub valid_from: Option<NaiveDate>,
+    pub valid_to: Option<NaiveDate>,
+    /// line numbers named in the text or in the attached PDF names, e.g. `101`, `S6`
+    pub lines: Vec<String>,
+    pub description: String,
+    pub pdfs: Vec<String>,
+}
+
+/// ===================== Dates =====================
+/// Czech date at the start of `s`: `17. 12. 2025`, `17.12.2025`; returns the date and its length.
+fn date_at(s: &str) -> Option<(NaiveDate, usize)> {
+    let mut rest = s;
+    let mut parts = Vec::new();
+
+    for max_digits in [2, 2, 4] {
+        let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        if digits == 0 || digits > max_digits {
+            return None;
+        }
+        parts.push(rest[..digits].parse::<u32>().ok()?);
+        rest = &rest[digits..];
+        if parts.len() < 3 {
+            rest = rest.strip_prefix('.')?.trim_start_matches(' ');
+        }
+    }
+
+    let date = NaiveDate::from_ymd_opt(parts[2] as i32, parts[1], parts[0])?;
+    Some((date, s.len() - rest.len()))
+}
+
+/// All dates in `text`, in order.
+fn find_dates(text: &str) -> Vec<NaiveDate> {
+    let mut dates = Vec::new();
+    let mut previous_digit = false;
+
+    for (i, c) in text.char_indices() {
+        if c.is_ascii_digit() && !previous_digit
+            && let Some((date, _)) = date_at(&text[i..])
+        {
+            dates.push(date);
+        }
+        previous_digit = c.is_ascii_digit();
+    }
+    dates
+}
+
+/// First date following one of `markers` (case-insensitive).
+fn date_after(text: &str, markers: &[&str]) -> Option<NaiveDate> {
+    let lower = text.to_lowercase();
+    markers
+        .iter()
+        .filter_map(|marker| {
+            let at = lower.find(marker)? + marker.len();
+            // `lower` keeps the byte offsets of `text` for the ASCII markers and digits used here
+            let rest = text.get(at..)?.trim_start_matches([' ', ':']);
+            date_at(rest).map(|(date, _)| (at, date))
+        })
+        .min_by_key(|(at, _)| *at)
+        .map(|(_, date)| date)
+}
+
+/// `Platnost: 1. 1. 2026 – 31. 1. 2026`, or `od 1. 1. 2026 do 31. 1. 2026` anywhere in the text.
+fn validity(text: &str) -> (Option<NaiveDate>, Option<NaiveDate>) {
+    if let Some(row) = text.lines().find(|row| {
+        let lower = row.to_lowercase();
+        lower.contains("platnost") || lower.contains("platí")
+    }) {
+        let dates = find_dates(row);
+        if !dates.is_empty() {
+            return (dates.first().copied(), dates.get(1).copied());
+        }
+    }
+    (date_after(text, &["od "]), date_after(text, &["do "]))
+}
+
+/// ===================== Lines =====================
+fn is_line_number(token: &str) -> bool {
+    let digits = token.trim_start_matches(['S', 'R', 'N', 'X']);
+    !digits.is_empty() && digits.len() <= 3 && digits.chars().all(|c| c.is_ascii_digit()) && token.len() - digits.len() <= 1
+}
+
+/// `linka`, `linky`, `linek`, `lince`, `linkami`, ...
+fn is_line_word(word: &str) -> bool {
+    let word = word.to_lowercase();
+    word.starts_with("link") || word == "linek" || word == "lince"
+}
+
+/// `row` with its dates blanked out, so their day and month are not read as line numbers.
+fn without_dates(row: &str) -> String {
+    let mut out = String::with_capacity(row.len());
+    let mut skip_to = 0;
+    let mut previous_digit = false;
+
+    for (i, c) in row.char_indices() {
+        if i >= skip_to && c.is_ascii_digit() && !previous_digit
+            && let Some((_, len)) = date_at(&row[i..])
+        {
+            skip_to = i + len;
+        }
+        out.push(if i < skip_to { ' ' } else { c });
+        previous_digit = c.is_ascii_digit();
+    }
+    out
+}
+
+/// Line numbers following a line word (`linky 37 a 38`), up to `od`/`do` or the next line word,
+/// plus the lines of the attached PDFs.
+fn affected_lines(text: &str, pdfs: &[String]) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut add = |line: String| {
+        if !lines.contains(&line) {
+            lines.push(line);
+        }
+    };
+
+    for row in text.lines().map(without_dates) {
+        let mut counting = false;
+        for token in row.split(|c: char| c.is_whitespace() || ",;:()/".contains(c)) {
+            let token = token.trim_end_matches('.');
+            if is_line_word(token) {
+                counting = true;
+            } else if token.eq_ignore_ascii_case("od") || token.eq_ignore_ascii_case("do") {
+                counting = false;
+            } else if counting && is_line_number(token) {
+                add(token.to_string());
+            }
+        }
+    }
+    for file in pdfs.iter().filter_map(|pdf| parse_timetable_url(pdf)) {
+        add(file.line);
+    }
+
+    lines.sort_by(|a, b| (a.len(), a).cmp(&(b.len(), b)));
+    lines
+}
+
+/// ===================== Notice =====================
+/// Builds a notice from what the scraper read off the page: the `h1` (if any), the visible text
+/// of the content and the PDF links. The publication date is the one after `Zveřejněno`/`Vydáno`,
+/// else the first date on the page.
+pub fn parse_change_notice(id: i32, url: &str, title: Option<String>, text: &str, pdfs: Vec<String>) -> ChangeNotice {
+    let title = title
+        .filter(|t| !t.trim().is_empty())
+        .or_else(|| text.lines().map(str::trim).find(|row| !row.is_empty()).map(str::to_string))
+        .unwrap_or_default();
+    let published = date_after(text, &["zveřejněno", "publikováno", "vydáno"]).or_else(|| find_dates(text).first().copied());
+    let (valid_from, valid_to) = validity(text);
+    let description = text
+        .lines()
+        .map(str::trim)
+        .filter(|row| !row.is_empty() && *row != title)
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    ChangeNotice {
+        id,
+        url: url.to_string(),
+        title: title.trim().to_string(),
+        published,
+        valid_from,
+        valid_to,
+        lines: affected_lines(text, &pdfs),
+        description,
+        pdfs,
+    }
+}
+
+/// ===================== Persistence =====================
+/// `change_notices.json` (the full notices) and `change_notices.csv` (one row per notice).
+pub fn save_notices(notices: &[ChangeNotice], dir: &str) -> Result<(), Box<dyn std::error::Error>> {
+    serialize_to_json(&notices, &format!("{}/change_notices.json", dir))?;
+
+    let date = |d: &Option<NaiveDate>| d.map(|d| d.to_string()).unwrap_or_default();
+    let csv = csv_table(
+        &["id", "title", "published", "valid_from", "valid_to", "lines", "pdfs", "url"],
+        notices.iter().map(|n| {
+            vec![
+                n.id.to_string(),
+                n.title.clone(),
+                date(&n.published),
+                date(&n.valid_from),
+                date(&n.valid_to),
+                n.lines.join(" "),
+                n.pdfs.len().to_string(),
+                n.url.clone(),
+            ]
+        }),
+    );
+    fs::write(format!("{}/change_notices.csv", dir), csv)?;
+    Ok(())
+}
+
+pub fn load_notices(dir: &str) -> Result<Vec<ChangeNotice>, Box<dyn std::error::Error>> {
+    deserialize_from_json(&format!("{}/change_notices.json", dir))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines_of(text: &str) -> Vec<String> {
+        parse_change_notice(1, "https://www.kodis.cz/changes/1", None, text, Vec::new()).lines
+    }
+
+    #[test]
+    fn dates_parse_with_and_without_spaces() {
+        let date = NaiveDate::from_ymd_opt(2025, 12, 17).unwrap();
+        assert_eq!(date_at("17. 12. 2025 a dál"), Some((date, 12)));
+        assert_eq!(date_at("17.12.2025"), Some((date, 10)));
+        assert_eq!(date_at("17. 12."), None);
+        assert_eq!(date_at("32.1.2025"), None);
+    }
+
+    #[test]
+    fn validity_range_is_read_from_the_text() {
+        let notice = parse_change_notice(1, "", None, "Výluka\nPlatnost: 1. 1. 2026 – 31. 1. 2026", Vec::new());
+        assert_eq!(notice.valid_from, NaiveDate::from_ymd_opt(2026, 1, 1));
+        assert_eq!(notice.valid_to, NaiveDate::from_ymd_opt(2026, 1, 31));
+    }
+
+    #[test]
+    fn lines_follow_a_line_word_and_skip_dates() {
+        assert_eq!(lines_of("Výluka linek 37 a 38"), ["37", "38"]);
+        assert_eq!(lines_of("Výluka linky 37 od 17. 12. 2025 do 20. 12. 2025"), ["37"]);
+        assert_eq!(lines_of("Linka 101: změna od 1. 3. 2026"), ["101"]);
+        assert_eq!(lines_of("Od 1. 3. jezdí na lince S6 nové spoje"), ["S6"]);
+        assert!(lines_of("Změna od 1. 3. 2026 v 5 hodin").is_empty());
+    }
+
+    #[test]
+    fn lines_of_attached_pdfs_are_included() {
+        let pdfs = vec!["https://kodis-files.s3.eu-central-1.amazonaws.com/101_2026_03_01_2026_12_12_0123456789.pdf".to_string()];
+        let notice = parse_change_notice(1, "", None, "Výluka linky 37", pdfs);
+        assert_eq!(notice.lines, ["37", "101"]);
+    }
+}
@@ -0,0 +1,182 @@
+use std::collections::BTreeMap;
+use std::fs;
+use chrono::NaiveDate;
+use serde::{Serialize, Deserialize};
+
+use crate::_02_serialization::{LinkKind, ScrapeResults, deserialize_from_json, serialize_to_json};
+use crate::_05_links::parse_timetable_url;
+use crate::_20_gtfs::csv_table;
+use crate::_23_change_notices::ChangeNotice;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct LineEntry {
+    /// timetable PDFs of the line, from the tabs and from change notices
+    pub timetables: Vec<String>,
+    /// IDs of the change notices affecting the line
+    pub notices: Vec<i32>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NoticeEntry {
+    pub title: String,
+    pub valid_from: Option<NaiveDate>,
+    pub lines: Vec<String>,
+    /// PDFs attached to the notice, plus tab timetables of its lines that start on its validity date
+    pub timetables: Vec<String>,
+}
+
+/// Ties the `/changes/{id}` phase to the line tabs: "which notices affect line X" and
+/// "which timetables came with notice N". Saved as `cross_reference.json`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct CrossReference {
+    pub lines: BTreeMap<String, LineEntry>,
+    pub notices: BTreeMap<i32, NoticeEntry>,
+}
+
+fn push_unique<T: PartialEq>(list: &mut Vec<T>, item: T) {
+    if !list.contains(&item) {
+        list.push(item);
+    }
+}
+
+pub fn build_index(notices: &[ChangeNotice], results: &ScrapeResults) -> CrossReference {
+    let mut index = CrossReference::default();
+
+    let timetables: Vec<(String, &String, NaiveDate)> = results
+        .links
+        .iter()
+        .filter(|link| link.kind == LinkKind::Pdf)
+        .filter_map(|link| parse_timetable_url(&link.url).map(|file| (file.line, &link.url, file.valid_from)))
+        .collect();
+    for (line, url, _) in &timetables {
+        push_unique(&mut index.lines.entry(line.clone()).or_default().timetables, (*url).clone());
+    }
+
+    for notice in notices {
+        let mut introduced = notice.pdfs.clone();
+        for (line, url, valid_from) in &timetables {
+            if notice.lines.contains(line) && notice.valid_from == Some(*valid_from) {
+                push_unique(&mut introduced, (*url).clone());
+            }
+        }
+
+        for line in &notice.lines {
+            let entry = index.lines.entry(line.clone()).or_default();
+            push_unique(&mut entry.notices, notice.id);
+        }
+        for url in &introduced {
+            if let Some(file) = parse_timetable_url(url) {
+                push_unique(&mut index.lines.entry(file.line).or_default().timetables, url.clone());
+            }
+        }
+
+        index.notices.insert(notice.id, NoticeEntry {
+            title: notice.title.clone(),
+            valid_from: notice.valid_from,
+            lines: notice.lines.clone(),
+            timetables: introduced,
+        });
+    }
+
+    for entry in index.lines.values_mut() {
+        entry.timetables.sort();
+        entry.notices.sort();
+    }
+    index
+}
+
+/// `cross_reference.json` (the full index) and `cross_reference.csv` (one row per line).
+pub fn save_index(index: &CrossReference, dir: &str) -> Result<(), Box<dyn std::error::Error>> {
+    serialize_to_json(index, &format!("{}/cross_reference.json", dir))?;
+
+    let csv = csv_table(
+        &["line", "notices", "timetables"],
+        index.lines.iter().map(|(line, entry)| {
+            vec![
+                line.clone(),
+                entry.notices.iter().map(i32::to_string).collect::<Vec<_>>().join(" "),
+                entry.timetables.join(" "),
+            ]
+        }),
+    );
+    fs::write(format!("{}/cross_reference.csv", dir), csv)?;
+    Ok(())
+}
+
+pub fn load_index(dir: &str) -> Result<CrossReference, Box<dyn std::error::Error>> {
+    deserialize_from_json(&format!("{}/cross_reference.json", dir))
+}
+
+/// "Which change notices affect line X"
+pub fn print_line(index: &CrossReference, line: &str) {
+    let Some(entry) = index.lines.get(line) else {
+        println!("Line {}: not in the index", line);
+        return;
+    };
+    println!("Line {}: {} timetable(s), {} change notice(s)", line, entry.timetables.len(), entry.notices.len());
+    for id in &entry.notices {
+        if let Some(notice) = index.notices.get(id) {
+            println!("  #{} {} (from {:?})", id, notice.title, notice.valid_from);
+        }
+    }
+    for url in &entry.timetables {
+        println!("  {}", url);
+    }
+}
+
+/// "Which timetables were introduced by notice N"
+pub fn print_notice(index: &CrossReference, id: i32) {
+    let Some(notice) = index.notices.get(&id) else {
+        println!("Change notice #{}: not in the index", id);
+        return;
+    };
+    println!("Change notice #{} {}: lines {}", id, notice.title, notice.lines.join(", "));
+    for url in &notice.timetables {
+        println!("  {}", url);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::_02_serialization::LinkOrigin;
+
+    const S3: &str = "https://kodis-files.s3.eu-central-1.amazonaws.com/";
+
+    fn notice(id: i32, lines: &[&str], valid_from: Option<NaiveDate>, pdfs: Vec<String>) -> ChangeNotice {
+        ChangeNotice {
+            id,
+            url: format!("https://www.kodis.cz/changes/{}", id),
+            title: format!("Změna {}", id),
+            published: None,
+            valid_from,
+            valid_to: None,
+            lines: lines.iter().map(|l| l.to_string()).collect(),
+            description: String::new(),
+            pdfs,
+        }
+    }
+
+    #[test]
+    fn lines_and_notices_point_at_each_other() {
+        let listed = format!("{}101_2026_03_01_2026_12_12_0123456789.pdf", S3);
+        let older = format!("{}101_2025_12_14_2026_02_28_9876543210.pdf", S3);
+        let attached = format!("{}37_2026_03_01_2026_12_12_aaaaaaaaaa.pdf", S3);
+        let results = ScrapeResults::from_tagged(
+            [&listed, &older].into_iter().map(|url| (url.clone(), LinkKind::Pdf, LinkOrigin::Dom)),
+        );
+        let notices = [
+            notice(2410, &["101", "37"], NaiveDate::from_ymd_opt(2026, 3, 1), vec![attached.clone()]),
+            notice(2411, &["101"], None, Vec::new()),
+        ];
+
+        let index = build_index(&notices, &results);
+
+        assert_eq!(index.lines["101"].notices, [2410, 2411]);
+        assert_eq!(index.lines["101"].timetables, [older, listed.clone()]);
+        assert_eq!(index.lines["37"].timetables, std::slice::from_ref(&attached));
+        // the listed timetable starts on the notice's validity date, so it came with it
+        assert_eq!(index.notices[&2410].timetables, [attached, listed]);
+        assert!(index.notices[&2411].timetables.is_empty());
+    }
+}
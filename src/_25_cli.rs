@@ -0,0 +1,220 @@
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::ops::RangeInclusive;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+
+use crate::_27_scrape_scope::{Phase, ScrapeScope, parse_id_range};
+
+/// Scrapes the KODIS timetable PDFs and publishes the list; every stage can run on its own
+/// against the results saved by the previous one.
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Cli {
+    #[command(flatten)]
+    pub shared: SharedOptions,
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Debug, Args)]
+pub struct SharedOptions {
+    /// Directory with `api_profiles.json` and `notifiers.json`
+    #[arg(long, global = true, default_value = ".")]
+    pub config: String,
+    /// Where results, reports, the PDF mirror and exports are written
+    #[arg(long, global = true, default_value = "CanopyResults")]
+    pub output_dir: String,
+    #[arg(long, global = true, value_enum, default_value_t = LogLevel::Info)]
+    pub log_level: LogLevel,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Scrape kodis.cz into scrape_results.json, change notices and the cross-reference index
+    Scrape(ScrapeArgs),
+    /// Drop old timetables into canopy_results.json and notify about changes since the last run
+    Filter(FilterArgs),
+    /// Mirror the PDFs, extract text, parse timetables and write the GTFS feed
+    Export(ExportArgs),
+    /// Content diff of the two latest mirrored versions of a line
+    Diff(DiffArgs),
+    /// Send canopy_results.json to the API
+    Upload(UploadArgs),
+    /// Check saved results: dead links, timetable parse problems, GTFS integrity
+    Validate(ValidateArgs),
+    /// Check config files, credentials, chromedriver and the output directory
+    Doctor,
+    /// Past runs of every command
+    History(HistoryArgs),
+    /// Which change notices affect a line, which timetables came with a notice
+    Index(IndexArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct ScrapeArgs {
+    /// Harvest PDF and data-API URLs from Chrome's network log as well
+    #[arg(long)]
+    pub capture_network: bool,
+    /// Save every visited page as a fixture bundle
+    #[arg(long)]
+    pub record: bool,
+    /// Scrape a recorded fixture bundle instead of kodis.cz, into `<output-dir>/replay/`
+    #[arg(long, value_name = "DIR")]
+    pub replay: Option<String>,
+    /// HEAD every PDF for its S3 size, Last-Modified and ETag
+    #[arg(long)]
+    pub enrich: bool,
+    /// Only these phases (default: all)
+    #[arg(long, value_enum, value_delimiter = ',')]
+    pub phase: Vec<Phase>,
+    /// Only these tabs: a tab (`MHD Ostrava`, `232-293`) or a kind (`city`, `region`, `train`, `boat`)
+    #[arg(long, value_name = "AREA")]
+    pub area: Vec<String>,
+    /// Only this range of change IDs, e.g. `2400-2450`
+    #[arg(long, value_name = "FROM-TO", value_parser = parse_id_range)]
+    pub change_ids: Option<RangeInclusive<i32>>,
+    /// Only links and change notices of these lines, e.g. `--line 101,S6`
+    #[arg(long, value_delimiter = ',')]
+    pub line: Vec<String>,
+}
+
+impl ScrapeArgs {
+    /// Any of these options makes the run partial: its results are merged into the saved snapshot.
+    pub fn scope(&self) -> ScrapeScope {
+        ScrapeScope {
+            phases: self.phase.clone(),
+            areas: self.area.clone(),
+            change_ids: self.change_ids.clone(),
+            lines: self.line.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct FilterArgs {
+    /// HEAD every PDF and record 404s and non-PDF answers in the run report
+    #[arg(long)]
+    pub check_links: bool,
+    /// Check the links and leave the dead ones out of canopy_results.json
+    #[arg(long)]
+    pub exclude_dead_links: bool,
+}
+
+/// Without any step flag, all steps run.
+#[derive(Debug, Args)]
+pub struct ExportArgs {
+    /// Download new and changed PDFs into the mirror
+    #[arg(long)]
+    pub mirror: bool,
+    #[arg(long, default_value_t = 8)]
+    pub mirror_concurrency: usize,
+    /// Extract the text of mirrored PDFs
+    #[arg(long)]
+    pub text: bool,
+    /// Parse the extracted text into stops and trips
+    #[arg(long)]
+    pub timetables: bool,
+    /// Write the GTFS feed
+    #[arg(long)]
+    pub gtfs: bool,
+    /// Write the line/change-notice index (`cross_reference.json` and `.csv`)
+    #[arg(long)]
+    pub index: bool,
+}
+
+impl ExportArgs {
+    pub fn all(&self) -> bool {
+        !(self.mirror || self.text || self.timetables || self.gtfs || self.index)
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct DiffArgs {
+    /// Line number, e.g. `101` or `S6`
+    pub line: String,
+}
+
+#[derive(Debug, Args)]
+pub struct UploadArgs {
+    /// Upload profile from api_profiles.json (default: the file's default profile)
+    #[arg(long, env = "API_PROFILE")]
+    pub profile: Option<String>,
+    /// Only show what would be sent
+    #[arg(long)]
+    pub dry_run: bool,
+    /// Only retry earlier failed uploads
+    #[arg(long)]
+    pub flush_outbox: bool,
+}
+
+/// Without any check flag, all checks run.
+#[derive(Debug, Args)]
+pub struct ValidateArgs {
+    #[arg(long)]
+    pub links: bool,
+    #[arg(long)]
+    pub timetables: bool,
+    #[arg(long)]
+    pub gtfs: bool,
+}
+
+impl ValidateArgs {
+    pub fn all(&self) -> bool {
+        !(self.links || self.timetables || self.gtfs)
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct HistoryArgs {
+    /// Number of most recent entries to show
+    #[arg(long, default_value_t = 20)]
+    pub limit: usize,
+}
+
+#[derive(Debug, Args)]
+#[group(required = true)]
+pub struct IndexArgs {
+    /// Change notices affecting this line
+    #[arg(long)]
+    pub line: Option<String>,
+    /// Timetables introduced by this change notice
+    #[arg(long)]
+    pub notice: Option<i32>,
+}
+
+/// ===================== Log level =====================
+/// Controls what a run prints, in the binary and the library modules alike; errors are always printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    /// also per-item details (every failed URL, every replaced file, ...)
+    Debug,
+}
+
+static LOG_LEVEL: AtomicU8 = AtomicU8::new(LogLevel::Info as u8);
+
+pub fn set_log_level(level: LogLevel) {
+    LOG_LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+pub fn log_enabled(level: LogLevel) -> bool {
+    level as u8 <= LOG_LEVEL.load(Ordering::Relaxed)
+}
+
+/// Shared by the binary and the library modules, so `--log-level` covers everything a run prints.
+#[macro_export]
+macro_rules! info {
+    ($($arg:tt)*) => { if $crate::_25_cli::log_enabled($crate::_25_cli::LogLevel::Info) { println!($($arg)*) } };
+}
+
+/// per-item details (every failed URL, every problem), only with `--log-level debug`
+#[macro_export]
+macro_rules! debug {
+    ($($arg:tt)*) => { if $crate::_25_cli::log_enabled($crate::_25_cli::LogLevel::Debug) { eprintln!($($arg)*) } };
+}
+
+#[macro_export]
+macro_rules! warn {
+    ($($arg:tt)*) => { if $crate::_25_cli::log_enabled($crate::_25_cli::LogLevel::Warn) { eprintln!($($arg)*) } };
+}
@@ -0,0 +1,33 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use serde::{Serialize, Deserialize};
+
+/// One CLI invocation, appended to `history.jsonl` in the output directory.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub run_id: String,
+    pub command: String,
+    pub ok: bool,
+    /// one-line outcome, e.g. `1234 links, +3 / -1` or the error
+    pub summary: String,
+}
+
+fn history_path(dir: &str) -> String {
+    format!("{}/history.jsonl", dir)
+}
+
+pub fn append_history(dir: &str, entry: &HistoryEntry) -> Result<(), Box<dyn std::error::Error>> {
+    fs::create_dir_all(dir)?;
+    let mut file = OpenOptions::new().create(true).append(true).open(history_path(dir))?;
+    writeln!(file, "{}", serde_json::to_string(entry)?)?;
+    Ok(())
+}
+
+/// All entries, oldest first; lines that do not parse are skipped.
+pub fn load_history(dir: &str) -> Vec<HistoryEntry> {
+    fs::read_to_string(history_path(dir))
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect()
+}
@@ -0,0 +1,232 @@
+use std::ops::RangeInclusive;
+use clap::ValueEnum;
+
+use crate::_05_links::{Area, MAIN_URLS, area_of_tab_url, get_change_ids, parse_timetable_url};
+use crate::_23_change_notices::ChangeNotice;
+
+/// The three passes of a full run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Phase {
+    /// `/changes/{id}` pages
+    Changes,
+    /// tabs with the `Budoucí jízdní řády` buttons opened
+    Future,
+    /// tabs as listed
+    Current,
+}
+
+/// Part of the site a run scrapes; every field left empty means "all of it".
+/// Anything but the full scope is merged into the saved snapshot instead of replacing it.
+/// Change pages belong to no tab, so `areas` without `phases` skips them (unless `change_ids` asks
+/// for some). `lines` cannot narrow them: a page's lines are only known once its text is read.
+#[derive(Debug, Clone, Default)]
+pub struct ScrapeScope {
+    pub phases: Vec<Phase>,
+    /// tab names (`MHD Ostrava`, `232-293`), kinds (`city`, `train`) or both (`city/MHD Ostrava`)
+    pub areas: Vec<String>,
+    pub change_ids: Option<RangeInclusive<i32>>,
+    /// only links and change notices of these lines
+    pub lines: Vec<String>,
+}
+
+/// `2400-2450` or a single `2410`
+pub fn parse_id_range(s: &str) -> Result<RangeInclusive<i32>, String> {
+    let (from, to) = s.split_once('-').unwrap_or((s, s));
+    let id = |n: &str| n.trim().parse::<i32>().map_err(|_| format!("not a change ID: {}", n));
+    let (from, to) = (id(from)?, id(to)?);
+    match from <= to {
+        true => Ok(from..=to),
+        false => Err(format!("empty range: {}", s)),
+    }
+}
+
+/// Case-insensitive match of an `--area` value against a tab.
+fn area_matches(area: &Area, filter: &str) -> bool {
+    let name = area.to_string();
+    let kind = name.split_once('/').map_or("", |(kind, _)| kind);
+    [kind, &area.tab, &name].iter().any(|candidate| candidate.eq_ignore_ascii_case(filter))
+}
+
+/// `S34` → (`S`, 34)
+fn split_number(s: &str) -> (&str, Option<u32>) {
+    let digits = s.trim_start_matches(|c: char| !c.is_ascii_digit());
+    (&s[..s.len() - digits.len()], digits.parse().ok())
+}
+
+/// Whether a tab can list `line`: tabs named by a range (`232-293`, `S1-S34`) list only lines
+/// in it, any other tab might list anything.
+fn tab_may_list(area: &Area, line: &str) -> bool {
+    let Some((from, to)) = area.tab.split_once('-') else {
+        return true;
+    };
+    match (split_number(from), split_number(to), split_number(line)) {
+        ((prefix, Some(from)), (to_prefix, Some(to)), (line_prefix, Some(n))) if prefix == to_prefix => {
+            line_prefix == prefix && (from..=to).contains(&n)
+        }
+        _ => true,
+    }
+}
+
+impl ScrapeScope {
+    pub fn is_full(&self) -> bool {
+        self.phases.is_empty() && self.areas.is_empty() && self.change_ids.is_none() && self.lines.is_empty()
+    }
+
+    pub fn runs(&self, phase: Phase) -> bool {
+        match phase {
+            Phase::Changes if self.phases.is_empty() => self.areas.is_empty() || self.change_ids.is_some(),
+            _ => self.phases.is_empty() || self.phases.contains(&phase),
+        }
+    }
+
+    /// IDs of `change_ids` that are no change page the scraper knows, to be reported before a run
+    /// scrapes nothing.
+    pub fn unknown_change_ids(&self) -> Vec<i32> {
+        let Some(range) = &self.change_ids else {
+            return Vec::new();
+        };
+        let known = get_change_ids();
+        range.clone().filter(|id| !known.contains(id)).collect()
+    }
+
+    /// `--area` values that match no tab, to be reported before a run scrapes nothing.
+    pub fn unknown_areas(&self) -> Vec<&String> {
+        let areas: Vec<Area> = MAIN_URLS.iter().filter_map(|url| area_of_tab_url(url)).collect();
+        self.areas
+            .iter()
+            .filter(|filter| !areas.iter().any(|area| area_matches(area, filter)))
+            .collect()
+    }
+
+    /// Tabs of `MAIN_URLS` in scope, in their usual order.
+    pub fn tab_urls(&self) -> Vec<&'static str> {
+        MAIN_URLS
+            .iter()
+            .copied()
+            .filter(|url| {
+                let Some(area) = area_of_tab_url(url) else {
+                    return true;
+                };
+                (self.areas.is_empty() || self.areas.iter().any(|filter| area_matches(&area, filter)))
+                    && (self.lines.is_empty() || self.lines.iter().any(|line| tab_may_list(&area, line)))
+            })
+            .collect()
+    }
+
+    pub fn change_ids(&self) -> Vec<i32> {
+        get_change_ids()
+            .into_iter()
+            .filter(|id| self.change_ids.as_ref().is_none_or(|range| range.contains(id)))
+            .collect()
+    }
+
+    /// With `lines`, only PDFs whose name carries one of them.
+    pub fn keeps_link(&self, url: &str) -> bool {
+        self.lines.is_empty() || parse_timetable_url(url).is_some_and(|file| self.lines.contains(&file.line))
+    }
+
+    pub fn keeps_notice(&self, notice: &ChangeNotice) -> bool {
+        self.lines.is_empty() || notice.lines.iter().any(|line| self.lines.contains(line))
+    }
+
+    /// `phases changes, areas train, lines 101 S6`, for the log and the history
+    pub fn describe(&self) -> String {
+        if self.is_full() {
+            return "full".to_string();
+        }
+        let mut parts = Vec::new();
+        if !self.phases.is_empty() {
+            let phases: Vec<String> = self.phases.iter().map(|p| format!("{:?}", p).to_lowercase()).collect();
+            parts.push(format!("phases {}", phases.join(" ")));
+        }
+        if !self.areas.is_empty() {
+            parts.push(format!("areas {}", self.areas.join(", ")));
+        }
+        if let Some(range) = &self.change_ids {
+            parts.push(format!("changes {}-{}", range.start(), range.end()));
+        }
+        if !self.lines.is_empty() {
+            parts.push(format!("lines {}", self.lines.join(" ")));
+        }
+        parts.join(", ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scope(areas: &[&str], lines: &[&str]) -> ScrapeScope {
+        ScrapeScope {
+            areas: areas.iter().map(|a| a.to_string()).collect(),
+            lines: lines.iter().map(|l| l.to_string()).collect(),
+            ..ScrapeScope::default()
+        }
+    }
+
+    #[test]
+    fn id_ranges_are_parsed() {
+        assert_eq!(parse_id_range("2400-2450"), Ok(2400..=2450));
+        assert_eq!(parse_id_range("2410"), Ok(2410..=2410));
+        assert!(parse_id_range("2450-2400").is_err());
+        assert!(parse_id_range("x-2400").is_err());
+    }
+
+    #[test]
+    fn change_ids_outside_the_known_set_are_reported() {
+        let known = ScrapeScope { change_ids: Some(2400..=2450), ..ScrapeScope::default() };
+        assert!(known.unknown_change_ids().is_empty());
+
+        let partly = ScrapeScope { change_ids: Some(2398..=2401), ..ScrapeScope::default() };
+        assert_eq!(partly.unknown_change_ids(), [2398, 2399]);
+    }
+
+    #[test]
+    fn areas_match_tab_kind_or_both() {
+        assert_eq!(scope(&["train"], &[]).tab_urls().len(), 2);
+        assert_eq!(scope(&["mhd ostrava"], &[]).tab_urls(), ["https://www.kodis.cz/lines/city?tab=MHD+Ostrava"]);
+        assert_eq!(scope(&["city/MHD Opava"], &[]).tab_urls(), ["https://www.kodis.cz/lines/city?tab=MHD+Opava"]);
+        assert_eq!(scope(&["train", "nowhere"], &[]).unknown_areas(), ["nowhere"]);
+    }
+
+    #[test]
+    fn lines_skip_tabs_whose_range_cannot_list_them() {
+        // tabs not named by a range (`75`, `NAD`) might list any line
+        let urls = scope(&["region", "train"], &["S6"]).tab_urls();
+        assert_eq!(urls, [
+            "https://www.kodis.cz/lines/region?tab=75",
+            "https://www.kodis.cz/lines/train?tab=S1-S34",
+            "https://www.kodis.cz/lines/region?tab=NAD",
+        ]);
+
+        let urls = scope(&["region"], &["240"]).tab_urls();
+        assert_eq!(urls, [
+            "https://www.kodis.cz/lines/region?tab=75",
+            "https://www.kodis.cz/lines/region?tab=232-293",
+            "https://www.kodis.cz/lines/region?tab=NAD",
+        ]);
+    }
+
+    #[test]
+    fn an_area_run_skips_change_pages_unless_asked_for() {
+        assert!(ScrapeScope::default().runs(Phase::Changes));
+        assert!(!scope(&["train"], &[]).runs(Phase::Changes));
+        assert!(scope(&["train"], &[]).runs(Phase::Current));
+        assert!(scope(&[], &["101"]).runs(Phase::Changes));
+
+        let with_ids = ScrapeScope { change_ids: Some(2400..=2410), ..scope(&["train"], &[]) };
+        assert!(with_ids.runs(Phase::Changes));
+        let explicit = ScrapeScope { phases: vec![Phase::Changes], ..scope(&["train"], &[]) };
+        assert!(explicit.runs(Phase::Changes) && !explicit.runs(Phase::Future));
+    }
+
+    #[test]
+    fn line_scope_keeps_only_their_timetables() {
+        let scope = scope(&[], &["101"]);
+        assert!(scope.keeps_link("https://kodis-files.s3.eu-central-1.amazonaws.com/101_2026_03_01_2026_12_12_0123456789.pdf"));
+        assert!(!scope.keeps_link("https://kodis-files.s3.eu-central-1.amazonaws.com/37_2026_03_01_2026_12_12_0123456789.pdf"));
+        assert!(!scope.keeps_link("https://www.kodis.cz/api/links"));
+        assert_eq!(scope.describe(), "lines 101");
+        assert_eq!(ScrapeScope::default().describe(), "full");
+    }
+}
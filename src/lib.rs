@@ -1,3 +1,25 @@
+pub mod _27_scrape_scope;
+pub mod _26_history;
+pub mod _25_cli;
+pub mod _24_cross_reference;
+pub mod _23_change_notices;
+pub mod _22_timetable_diff;
+pub mod _21_object_meta;
+pub mod _20_gtfs;
+pub mod _19_timetable;
+pub mod _18_pdf_text;
+pub mod _17_link_check;
+pub mod _16_mirror;
+pub mod _15_notify;
+pub mod _14_outbox;
+pub mod _13_signing;
+pub mod _12_dry_run;
+pub mod _11_reconcile;
+pub mod _10_delta;
+pub mod _09_api_profiles;
+pub mod _08_fixtures;
+pub mod _07_network_log;
+pub mod _06_run_report;
 pub mod _05_links;
 pub mod _04_scraping_chrome;
 pub mod _03_scraping_edge;
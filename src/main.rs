@@ -1,27 +1,55 @@
 //cd c:/temp
 //chromedriver.exe --port=9515
 
-mod _01_http_client;
-mod _02_serialization;
-mod _03_scraping_edge;
-mod _04_scraping_chrome;
-mod _05_links;
+// The modules live in the library crate (lib.rs), so their public API (e.g. the signature
+// verification helper) is usable by other crates and not dead code here.
+use thirty_four_from_canopy_rewrite::{
+    _01_http_client, _02_serialization, _04_scraping_chrome, _06_run_report, _08_fixtures,
+    _09_api_profiles, _10_delta, _11_reconcile, _12_dry_run, _14_outbox, _15_notify,
+    _16_mirror, _17_link_check, _18_pdf_text, _19_timetable, _20_gtfs,
+    _21_object_meta, _22_timetable_diff, _23_change_notices,
+    _24_cross_reference, _25_cli, _26_history, debug, info, warn,
+};
 
+use std::collections::HashSet;
 use std::fs;
+use std::path::Path;
 use std::time::{Instant, Duration};
+use clap::Parser;
 
-use _01_http_client::put_to_rest_api;
-use _02_serialization::serialize_to_json;
+use _01_http_client::{ApiClient, UploadOutcome, upload_results};
+use _02_serialization::{LinksPayload, ScrapeResults, deserialize_from_json, serialize_to_json};
 //use _03_scraping_edge::scrape_real_results_edge;
-use _04_scraping_chrome::scrape_real_results_chrome;
+use _04_scraping_chrome::{CHROMEDRIVER_URL, ScrapeOptions, scrape_real_results_chrome};
+use _06_run_report::{RunReport, new_run_id};
+use _08_fixtures::{FixtureBundle, serve_bundle};
+use _09_api_profiles::ApiProfiles;
+use _11_reconcile::{report_drift, verify_upload};
+use _12_dry_run::{plan_upload, print_plan, write_dry_run};
+use _10_delta::compute_delta;
+use _14_outbox::Outbox;
+use _15_notify::{Notification, Notifier, NotifyConfig, expiring_timetables, notify_all};
+use _16_mirror::mirror_pdfs;
+use _17_link_check::check_links;
+use _18_pdf_text::extract_mirror;
+use _19_timetable::{ParseReport, parse_mirror};
+use _20_gtfs::{apply_coordinates, build_feed, load_stop_coordinates, validate_feed, write_feed};
+use _21_object_meta::{carry_over, enrich_links, replaced_objects, size_by_area};
+use _22_timetable_diff::{diff_latest, print_diff};
+use _23_change_notices::{load_notices, save_notices};
+use _24_cross_reference::{build_index, load_index, print_line, print_notice, save_index};
+use _25_cli::{
+    Cli, Command, DiffArgs, ExportArgs, FilterArgs, HistoryArgs, IndexArgs, ScrapeArgs,
+    UploadArgs, ValidateArgs, set_log_level,
+};
+use _26_history::{HistoryEntry, append_history, load_history};
+
+/// One-line summary of what a command did, recorded in the history
+type CommandResult = Result<String, Box<dyn std::error::Error>>;
 
 // Filter logic (same as you had)
-fn filter_old_links(mut payload: crate::_02_serialization::LinksPayload) -> crate::_02_serialization::LinksPayload {
-    payload.list = payload
-        .list
-        .into_iter()
-        .filter(|link| !link.contains("2022") && !link.contains("2023"))
-        .collect();
+fn filter_old_links(mut payload: LinksPayload) -> LinksPayload {
+    payload.list.retain(|link| !link.contains("2022") && !link.contains("2023"));
     payload
 }
 
@@ -39,19 +67,512 @@ fn format_duration(duration: Duration) -> String {
     }
 }
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    println!("Starting Scraper...");
-    let start = Instant::now();
+/// Where every command reads and writes, from the shared options.
+struct Context {
+    config_dir: String,
+    output_dir: String,
+    run_id: String,
+}
 
-    // 1. Scrape (using Chrome instead of Edge)
-    let payload = match scrape_real_results_chrome().await {
-        Ok(p) => p,
+impl Context {
+    fn out(&self, name: &str) -> String {
+        format!("{}/{}", self.output_dir, name)
+    }
+
+    fn config(&self, name: &str) -> String {
+        format!("{}/{}", self.config_dir, name)
+    }
+
+    /// Same run writing into `<output-dir>/replay/`, so replayed results never reach `filter` or `upload`.
+    fn replay(&self) -> Context {
+        Context {
+            config_dir: self.config_dir.clone(),
+            output_dir: self.out("replay"),
+            run_id: self.run_id.clone(),
+        }
+    }
+
+    fn mirror_dir(&self) -> String {
+        self.out("pdfs")
+    }
+
+    fn notify_config(&self) -> Result<NotifyConfig, Box<dyn std::error::Error>> {
+        NotifyConfig::load(&self.config("notifiers.json"))
+    }
+
+    /// Expiry window and notifiers from `notifiers.json`, best effort: a broken config is logged
+    /// and nothing is sent, so it never hides what the command itself did.
+    fn notifiers(&self) -> (i64, Vec<Box<dyn Notifier>>) {
+        let config = self.notify_config().unwrap_or_else(|e| {
+            warn!("Notifications disabled, notifiers.json: {}", e);
+            NotifyConfig::default()
+        });
+        match config.build() {
+            Ok(notifiers) => (config.expiring_within_days, notifiers),
+            Err(e) => {
+                warn!("Notifications disabled, notifiers.json: {}", e);
+                (config.expiring_within_days, Vec::new())
+            }
+        }
+    }
+
+    /// Dead-link check: HEADs every link and records the problems in the last scrape's run report.
+    async fn run_link_check(&self, links: &[String]) -> Result<RunReport, Box<dyn std::error::Error>> {
+        info!("Checking {} links...", links.len());
+        let mut report = self.run_report();
+        let checks = check_links(links, 16).await?;
+        report.links_checked = checks.len();
+        report.link_problems = checks.into_iter().filter(|c| c.problem.is_some()).collect();
+        serialize_to_json(&report, &self.out("run_report.json"))?;
+
+        info!("Links: {} checked, {} with problems", report.links_checked, report.link_problems.len());
+        for check in &report.link_problems {
+            debug!("  {:?} {} ({:?})", check.problem, check.url, check.status);
+        }
+        Ok(report)
+    }
+
+    /// The last scrape's report, so the link check adds to it instead of starting a new one.
+    fn run_report(&self) -> RunReport {
+        deserialize_from_json(&self.out("run_report.json")).unwrap_or_else(|_| RunReport::start(&self.output_dir))
+    }
+
+    fn load_results(&self) -> Result<ScrapeResults, Box<dyn std::error::Error>> {
+        deserialize_from_json(&self.out("scrape_results.json"))
+            .map_err(|e| format!("cannot read scrape_results.json ({}), run `scrape` first", e).into())
+    }
+
+    fn load_filtered(&self) -> Result<LinksPayload, Box<dyn std::error::Error>> {
+        deserialize_from_json(&self.out("canopy_results.json"))
+            .map_err(|e| format!("cannot read canopy_results.json ({}), run `filter` first", e).into())
+    }
+}
+
+/// ===================== scrape =====================
+async fn scrape(ctx: &Context, args: &ScrapeArgs) -> CommandResult {
+    let replay_ctx = args.replay.as_ref().map(|_| ctx.replay());
+    let ctx = replay_ctx.as_ref().unwrap_or(ctx);
+    fs::create_dir_all(&ctx.output_dir)?;
+    let mut report = RunReport::start(&ctx.output_dir);
+    let scope = args.scope();
+    let unknown = scope.unknown_areas();
+    if !unknown.is_empty() {
+        return Err(format!("no tab matches --area {:?}", unknown).into());
+    }
+    let unknown_ids = scope.unknown_change_ids();
+    if let (Some(first), Some(last)) = (unknown_ids.first(), unknown_ids.last()) {
+        return Err(format!("--change-ids: {} ID(s) are no known change page ({} to {})", unknown_ids.len(), first, last).into());
+    }
+    let mut options = ScrapeOptions {
+        capture_network: args.capture_network,
+        record_dir: args.record.then(|| ctx.out(&format!("fixtures/{}", report.run_id))),
+        scope: scope.clone(),
+        ..ScrapeOptions::default()
+    };
+    if !scope.is_full() {
+        info!("Partial scrape ({}), merged into the saved results", scope.describe());
+    }
+
+    // Replay: serve a recorded bundle locally instead of visiting kodis.cz
+    if let Some(dir) = &args.replay {
+        options.site_base_url = serve_bundle(FixtureBundle::load(dir)?).await?;
+        info!("Replaying {} from {}", dir, options.site_base_url);
+        info!("Replayed results go to {}, pass it as --output-dir to work with them", ctx.output_dir);
+    }
+
+    // Scrape (using Chrome instead of Edge)
+    let scraped = scrape_real_results_chrome(&mut report, &options).await;
+
+    serialize_to_json(&report, &ctx.out("run_report.json"))?;
+    if !report.failures.is_empty() {
+        info!("{} failure artifact(s) saved in {}", report.failures.len(), report.artifacts_dir);
+    }
+
+    let mut results = match scraped {
+        Ok(r) => r,
         Err(e) => {
-            eprintln!("Scraping failed: {}. Is chromedriver running on port 9515?", e);
-            return Ok(());
+            let (_, notifiers) = ctx.notifiers();
+            notify_all(&notifiers, &Notification::failure(&report.run_id, &e.to_string())).await;
+            return Err(format!("Scraping failed: {}. Is chromedriver running on port 9515?", e).into());
         }
     };
+
+    // S3 headers for every PDF: catches files replaced behind an unchanged URL
+    let previous_results: Option<ScrapeResults> = deserialize_from_json(&ctx.out("scrape_results.json")).ok();
+    if args.enrich {
+        let failed = enrich_links(&mut results, 16).await?;
+        info!("Object metadata: {} failed", failed.len());
+        for (url, error) in &failed {
+            debug!("  {}: {}", url, error);
+        }
+
+        if let Some(previous) = &previous_results {
+            report.replaced_objects = replaced_objects(previous, &results);
+            serialize_to_json(&report, &ctx.out("run_report.json"))?;
+            for replaced in &report.replaced_objects {
+                warn!("  Replaced: {} ({:?} → {:?})", replaced.url, replaced.before.etag, replaced.after.etag);
+            }
+        }
+        for (area, size) in size_by_area(&results) {
+            info!(
+                "  {}: {} file(s), {:.1} MB{}",
+                area, size.files, size.bytes as f64 / 1_000_000.0,
+                match size.unknown {
+                    0 => String::new(),
+                    n => format!(" ({} without size)", n),
+                }
+            );
+        }
+    }
+    if let Some(previous) = &previous_results {
+        carry_over(previous, &mut results);
+    }
+
+    // A partial run only adds to the snapshot; links gone from the site drop out on the next full run
+    let found = (results.links.len(), results.notices.len());
+    if !scope.is_full()
+        && let Some(mut snapshot) = previous_results
+    {
+        snapshot.notices = load_notices(&ctx.output_dir).unwrap_or_default();
+        snapshot.merge(results);
+        results = snapshot;
+        info!("Merged: {} links, {} change notices in the snapshot", results.links.len(), results.notices.len());
+    }
+
+    serialize_to_json(&results, &ctx.out("scrape_results.json"))?;
+    save_notices(&results.notices, &ctx.output_dir)?;
+    let index = build_index(&results.notices, &results);
+    save_index(&index, &ctx.output_dir)?;
+    info!("Change notices: {}, lines indexed: {}", results.notices.len(), index.lines.len());
+
+    Ok(format!("{}: {} links, {} change notices", scope.describe(), found.0, found.1))
+}
+
+/// ===================== filter =====================
+async fn filter(ctx: &Context, args: &FilterArgs) -> CommandResult {
+    let mut filtered = filter_old_links(ctx.load_results()?.to_payload());
+
+    // Dead-link check: HEAD every PDF, flag 404s and non-PDF answers in the run report
+    if args.exclude_dead_links || args.check_links {
+        let report = ctx.run_link_check(&filtered.list).await?;
+        if args.exclude_dead_links {
+            let dead: HashSet<&String> = report.link_problems.iter().filter(|c| c.is_dead()).map(|c| &c.url).collect();
+            filtered.list.retain(|url| !dead.contains(url));
+            info!("Excluded {} dead link(s) from the upload", dead.len());
+        }
+    }
+
+    // Save (the diff against the previous run's list is taken first, it is gone once the file is written)
+    let previous: Option<LinksPayload> = deserialize_from_json(&ctx.out("canopy_results.json")).ok();
+    let diff = previous.map(|previous| compute_delta(&previous, &filtered)).transpose()?;
+    serialize_to_json(&filtered, &ctx.out("canopy_results.json"))?;
+
+    let mut summary = format!("{} links", filtered.list.len());
+    if let Some(diff) = &diff {
+        summary.push_str(&format!(", +{} / -{}", diff.added.len(), diff.removed.len()));
+        if !diff.is_empty() {
+            let (expiring_within_days, notifiers) = ctx.notifiers();
+            let today = chrono::Local::now().date_naive();
+            let expiring = expiring_timetables(&filtered, today, expiring_within_days);
+            notify_all(&notifiers, &Notification::changes(&ctx.run_id, diff, expiring)).await;
+        }
+    }
+
+    info!("Filtered: {}", summary);
+    Ok(summary)
+}
+
+/// ===================== export =====================
+async fn export(ctx: &Context, args: &ExportArgs) -> CommandResult {
+    let mut done = Vec::new();
+
+    // Keep our own copy of every PDF, KODIS deletes old ones from S3
+    if args.all() || args.mirror {
+        let filtered = ctx.load_filtered()?;
+        info!("Mirroring PDFs ({} at a time)...", args.mirror_concurrency);
+        let summary = mirror_pdfs(&filtered.list, &ctx.mirror_dir(), args.mirror_concurrency).await?;
+        info!(
+            "Mirror: {} downloaded, {} resumed, {} unchanged, {} failed",
+            summary.downloaded, summary.resumed, summary.unchanged, summary.failed.len()
+        );
+        for (url, error) in &summary.failed {
+            debug!("  {}: {}", url, error);
+        }
+        done.push(format!("{} downloaded", summary.downloaded + summary.resumed));
+    }
+
+    // Text of every mirrored PDF, stored next to it
+    if args.all() || args.text {
+        let summary = extract_mirror(&ctx.mirror_dir())?;
+        info!(
+            "Text extraction: {} extracted, {} up to date, {} failed",
+            summary.extracted, summary.up_to_date, summary.failed.len()
+        );
+        for (file, error) in &summary.failed {
+            debug!("  {}: {}", file, error);
+        }
+        done.push(format!("{} extracted", summary.extracted));
+    }
+
+    // Stops, trips and footnotes parsed from the extracted text
+    if args.all() || args.timetables {
+        let reports = parse_mirror(&ctx.mirror_dir())?;
+        serialize_to_json(&reports, &ctx.out("timetable_report.json"))?;
+        let trips: usize = reports.iter().map(|r| r.trips).sum();
+        let unclean = reports.iter().filter(|r| !r.is_clean()).count();
+        info!("Timetables: {} parsed, {} trips, {} with problems", reports.len(), trips, unclean);
+        for report in reports.iter().filter(|r| !r.is_clean()) {
+            debug!(
+                "  {}: {} stop(s) without data, {:?}",
+                report.file, report.stops_without_data.len(), report.layout_problems
+            );
+        }
+        done.push(format!("{} timetables", reports.len()));
+    }
+
+    // GTFS static feed from the parsed timetables
+    if args.all() || args.gtfs {
+        let (mut feed, summary) = build_feed(&ctx.load_results()?, &ctx.load_filtered()?.list, &ctx.mirror_dir());
+        let without_coordinates = apply_coordinates(&mut feed, &load_stop_coordinates(&ctx.config("stop_coordinates.csv"))?);
+        if without_coordinates > 0 {
+            warn!("GTFS: {} stop(s) without coordinates, add them to stop_coordinates.csv", without_coordinates);
+        }
+        let problems = validate_feed(&feed);
+        write_feed(&feed, &ctx.out("gtfs"))?;
+        info!(
+            "GTFS: {} timetable(s), {} routes, {} stops, {} trips, {} skipped, {} integrity problem(s)",
+            summary.timetables, feed.routes.len(), feed.stops.len(), feed.trips.len(),
+            summary.skipped.len(), problems.len()
+        );
+        for problem in &problems {
+            debug!("  {}", problem);
+        }
+        done.push(format!("GTFS {} trips", feed.trips.len()));
+    }
+
+    // Line ↔ change notice index, rebuilt from the saved results
+    if args.all() || args.index {
+        let mut results = ctx.load_results()?;
+        results.notices = load_notices(&ctx.output_dir).unwrap_or_default();
+        let index = build_index(&results.notices, &results);
+        save_index(&index, &ctx.output_dir)?;
+        info!("Cross-reference: {} lines, {} change notices", index.lines.len(), index.notices.len());
+        done.push(format!("index {} lines", index.lines.len()));
+    }
+
+    Ok(done.join(", "))
+}
+
+/// ===================== diff =====================
+fn diff(ctx: &Context, args: &DiffArgs) -> CommandResult {
+    let diff = diff_latest(&ctx.mirror_dir(), &args.line)?;
+    print_diff(&diff);
+    serialize_to_json(&diff, &ctx.out(&format!("diff_{}.json", args.line)))?;
+    Ok(format!("line {}: {} → {}", diff.line, diff.before, diff.after))
+}
+
+/// ===================== upload =====================
+async fn upload(ctx: &Context, args: &UploadArgs) -> CommandResult {
+    // Upload target: --profile, else API_PROFILE, else the file's default (prod)
+    let profiles = ApiProfiles::load(&ctx.config("api_profiles.json"))?;
+    let profile = profiles.select(args.profile.as_deref())?;
+    let outbox = Outbox::open(&ctx.out("outbox"))?;
+
+    // Only retry earlier failed uploads
+    if args.flush_outbox {
+        let summary = outbox.flush(&ApiClient::new(profile)?.with_results_dir(&ctx.output_dir)).await?;
+        info!("Outbox: {} delivered, {} still pending", summary.delivered, summary.pending);
+        return Ok(format!("outbox {}: {} delivered, {} pending", profile.name, summary.delivered, summary.pending));
+    }
+
+    let filtered = ctx.load_filtered()?;
+
+    // Dry run: only show what would be sent
+    if args.dry_run {
+        let plan = plan_upload(profile, &filtered, &ctx.output_dir)?;
+        write_dry_run(&plan, &ctx.output_dir)?;
+        print_plan(&plan);
+        return Ok(format!("dry run {}: {} links", profile.name, filtered.list.len()));
+    }
+
+    info!("Sending to API ({} → {}, key: {})...", profile.name, profile.url, profile.credential.describe());
+    let client = ApiClient::new(profile)?.with_results_dir(&ctx.output_dir);
+
+    // earlier runs first, so the server sees the snapshots in order
+    let flushed = outbox.flush(&client).await?;
+    if flushed.delivered > 0 || flushed.pending > 0 {
+        info!("Outbox: {} delivered, {} still pending", flushed.delivered, flushed.pending);
+    }
+
+    report_drift(&client, &filtered, "before upload").await;
+
+    let outcome = match upload_results(&client, &filtered).await {
+        Ok(outcome) => outcome,
+        Err(e) => {
+            let path = outbox.enqueue(&ctx.run_id, &profile.name, &filtered, &e.to_string())?;
+            warn!("Upload failed, queued in {}", path.display());
+            return Err(e);
+        }
+    };
+
+    // this snapshot is on the server now, anything still queued is older
+    let stale = outbox.drop_stale(&profile.name)?;
+    if stale > 0 {
+        info!("Outbox: dropped {} stale snapshot(s)", stale);
+    }
+
+    let summary = match outcome {
+        UploadOutcome::Sent(response) => {
+            info!("Response: {} - {}", response.message1, response.message2);
+            if !response.extra.is_empty() {
+                info!("Response extras: {}", serde_json::Value::Object(response.extra));
+            }
+            format!("{}: {} links sent", profile.name, filtered.list.len())
+        }
+        UploadOutcome::Unchanged => {
+            info!("Nothing changed since the last upload, skipped");
+            format!("{}: unchanged", profile.name)
+        }
+    };
+
+    // Read back what the server stored
+    verify_upload(&client, &filtered).await?;
+    Ok(summary)
+}
+
+/// ===================== validate =====================
+/// Any problem found makes the command fail, so it can gate a scheduled job.
+async fn validate(ctx: &Context, args: &ValidateArgs) -> CommandResult {
+    let mut found = Vec::new();
+
+    if args.all() || args.links {
+        let report = ctx.run_link_check(&ctx.load_filtered()?.list).await?;
+        if !report.link_problems.is_empty() {
+            found.push(format!("{} link(s)", report.link_problems.len()));
+        }
+    }
+
+    if args.all() || args.timetables {
+        let reports: Vec<ParseReport> = deserialize_from_json(&ctx.out("timetable_report.json"))
+            .map_err(|e| format!("cannot read timetable_report.json ({}), run `export` first", e))?;
+        let unclean: Vec<&ParseReport> = reports.iter().filter(|r| !r.is_clean()).collect();
+        info!("Timetables: {} parsed, {} with problems", reports.len(), unclean.len());
+        for report in &unclean {
+            debug!("  {}: {:?} {:?}", report.file, report.stops_without_data, report.layout_problems);
+        }
+        if !unclean.is_empty() {
+            found.push(format!("{} timetable(s)", unclean.len()));
+        }
+    }
+
+    if args.all() || args.gtfs {
+        let (mut feed, _) = build_feed(&ctx.load_results()?, &ctx.load_filtered()?.list, &ctx.mirror_dir());
+        apply_coordinates(&mut feed, &load_stop_coordinates(&ctx.config("stop_coordinates.csv"))?);
+        let problems = validate_feed(&feed);
+        info!("GTFS: {} trips, {} integrity problem(s)", feed.trips.len(), problems.len());
+        for problem in &problems {
+            debug!("  {}", problem);
+        }
+        if !problems.is_empty() {
+            found.push(format!("{} GTFS integrity", problems.len()));
+        }
+    }
+
+    if found.is_empty() {
+        Ok("no problems".to_string())
+    } else {
+        Err(format!("problems: {}", found.join(", ")).into())
+    }
+}
+
+/// ===================== doctor =====================
+async fn doctor(ctx: &Context) -> CommandResult {
+    let mut checks: Vec<(String, Result<String, String>)> = Vec::new();
+
+    let probe = Path::new(&ctx.output_dir).join(".doctor");
+    let writable = fs::write(&probe, b"ok").and_then(|_| fs::remove_file(&probe));
+    checks.push(("output dir".into(), writable.map(|_| ctx.output_dir.clone()).map_err(|e| e.to_string())));
+
+    match ApiProfiles::load(&ctx.config("api_profiles.json")) {
+        Ok(profiles) => {
+            let outbox = Outbox::open(&ctx.out("outbox"));
+            for profile in &profiles.profiles {
+                let signing = match &profile.signing {
+                    Some(signing) => signing.secret.resolve().map(|_| ()),
+                    None => Ok(()),
+                };
+                let result = match (profile.credential.resolve(), signing) {
+                    (Err(e), _) => Err(format!("key ({}): {}", profile.credential.describe(), e)),
+                    (_, Err(e)) => Err(format!("signing secret: {}", e)),
+                    (Ok(_), Ok(_)) => match outbox.as_ref().map(|o| o.pending(&profile.name)) {
+                        Ok(Ok(0)) => Ok(profile.url.clone()),
+                        Ok(Ok(n)) => Err(format!("{} upload(s) waiting in the outbox, run `upload --flush-outbox`", n)),
+                        Ok(Err(e)) => Err(format!("outbox: {}", e)),
+                        Err(e) => Err(format!("outbox: {}", e)),
+                    },
+                };
+                checks.push((format!("profile {}", profile.name), result));
+            }
+        }
+        Err(e) => checks.push(("api_profiles.json".into(), Err(e.to_string()))),
+    }
+
+    let notifiers = ctx.notify_config().and_then(|config| config.build());
+    let notifiers = notifiers.map(|notifiers| format!("{} notifier(s)", notifiers.len()));
+    checks.push(("notifiers.json".into(), notifiers.map_err(|e| e.to_string())));
+
+    let status = reqwest::Client::new()
+        .get(format!("{}/status", CHROMEDRIVER_URL))
+        .timeout(Duration::from_secs(3))
+        .send()
+        .await;
+    let chromedriver = match status {
+        Ok(response) if response.status().is_success() => Ok(CHROMEDRIVER_URL.to_string()),
+        Ok(response) => Err(format!("{} answered {}", CHROMEDRIVER_URL, response.status())),
+        Err(_) => Err(format!("not reachable at {}", CHROMEDRIVER_URL)),
+    };
+    checks.push(("chromedriver".into(), chromedriver));
+
+    for file in ["scrape_results.json", "canopy_results.json"] {
+        let saved = fs::metadata(ctx.out(file)).and_then(|m| m.modified());
+        let result = saved
+            .map(|time| format!("saved {}", chrono::DateTime::<chrono::Local>::from(time).format("%Y-%m-%d %H:%M")))
+            .map_err(|_| "missing".to_string());
+        checks.push((file.into(), result));
+    }
+
+    for (name, result) in &checks {
+        match result {
+            Ok(detail) => println!("  ok    {}: {}", name, detail),
+            Err(problem) => println!("  FAIL  {}: {}", name, problem),
+        }
+    }
+    match checks.iter().filter(|(_, result)| result.is_err()).count() {
+        0 => Ok(format!("{} checks passed", checks.len())),
+        n => Err(format!("{} of {} checks failed", n, checks.len()).into()),
+    }
+}
+
+/// ===================== history / index =====================
+fn history(ctx: &Context, args: &HistoryArgs) {
+    let entries = load_history(&ctx.output_dir);
+    for entry in &entries[entries.len().saturating_sub(args.limit)..] {
+        let status = if entry.ok { "ok" } else { "FAILED" };
+        println!("{}  {:<8} {:<6} {}", entry.run_id, entry.command, status, entry.summary);
+    }
+}
+
+fn index(ctx: &Context, args: &IndexArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let index = load_index(&ctx.output_dir)?;
+    if let Some(line) = &args.line {
+        print_line(&index, line);
+    }
+    if let Some(id) = args.notice {
+        print_notice(&index, id);
+    }
+    Ok(())
+}
+
 /*
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("Starting Scraper...");
@@ -67,20 +588,47 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
  */
 
-    // 2. Filter
-    let filtered = filter_old_links(payload);
-
-    // 3. Save
-    fs::create_dir_all("CanopyResults")?;
-    serialize_to_json(&filtered, "CanopyResults/canopy_results.json")?;
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let cli = Cli::parse();
+    set_log_level(cli.shared.log_level);
+    let ctx = Context {
+        config_dir: cli.shared.config,
+        output_dir: cli.shared.output_dir,
+        run_id: new_run_id(),
+    };
+    fs::create_dir_all(&ctx.output_dir)?;
+    let start = Instant::now();
 
-    // 4. Send to API
-    println!("Sending to API...");
-    let response = put_to_rest_api().await?;
-    println!("Response: {} - {}", response.message1, response.message2);
+    let (name, result) = match &cli.command {
+        Command::Scrape(args) => ("scrape", scrape(&ctx, args).await),
+        Command::Filter(args) => ("filter", filter(&ctx, args).await),
+        Command::Export(args) => ("export", export(&ctx, args).await),
+        Command::Diff(args) => ("diff", diff(&ctx, args)),
+        Command::Upload(args) => ("upload", upload(&ctx, args).await),
+        Command::Validate(args) => ("validate", validate(&ctx, args).await),
+        Command::Doctor => ("doctor", doctor(&ctx).await),
+        // queries only, not worth a history entry
+        Command::History(args) => {
+            history(&ctx, args);
+            return Ok(());
+        }
+        Command::Index(args) => return index(&ctx, args),
+    };
 
-    let elapsed = start.elapsed();
-    println!("Took: {}", format_duration(elapsed));
+    let entry = HistoryEntry {
+        run_id: ctx.run_id.clone(),
+        command: name.to_string(),
+        ok: result.is_ok(),
+        summary: match &result {
+            Ok(summary) => summary.clone(),
+            Err(e) => e.to_string(),
+        },
+    };
+    if let Err(e) = append_history(&ctx.output_dir, &entry) {
+        warn!("Cannot write history: {}", e);
+    }
 
-    Ok(())
-}
\ No newline at end of file
+    info!("Took: {}", format_duration(start.elapsed()));
+    result.map(|_| ())
+}